@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::poller::{ServerStatus, Statuses};
+use crate::steamid::SteamId;
+use crate::tags::Tags;
+
+/// Answers a GET of `url` from the cached statuses, returns the status code and JSON body.
+/// `/servers?tag=cp&tag=eu` only lists the servers that have all of the tags,
+/// `/servers?steam_id=[G:1:N]` the one with that SteamID, whatever its address.
+pub fn route(url: &str, statuses: &[ServerStatus], now: DateTime<Utc>) -> (u16, Value) {
+    let mut url = url.splitn(2, '?');
+    let path = url.next().unwrap_or("").trim_end_matches('/');
+    let query = url.next().unwrap_or("");
+    let tags = query_values(query, "tag");
+    let steam_ids = match query_values(query, "steam_id").iter().map(|value| value.parse::<SteamId>()).collect::<Result<Vec<_>, _>>() {
+        Ok(steam_ids) => steam_ids,
+        Err(err) => return (400, json!({ "error": err })),
+    };
+    // Addresses in the path may be escaped, `/servers/%5B::1%5D:27015`.
+    let parts: Vec<String> = path.split('/').skip(1).map(percent_decode).collect();
+    let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+
+    let find = |address: &str| statuses.iter().find(|status| status.address == address);
+
+    match parts.as_slice() {
+        ["servers"] => (200, Value::Array(statuses
+            .iter()
+            .filter(|status| tags.is_empty() || status.server_info.as_ref().is_some_and(|server_info| Tags::from_server_info(server_info).contains_all(&tags)))
+            .filter(|status| steam_ids.is_empty() || status.server_info.as_ref().and_then(|server_info| server_info.steam_id).is_some_and(|steam_id| steam_ids.iter().any(|wanted| wanted.same_account(&steam_id))))
+            .map(|status| server(status, now))
+            .collect())),
+        ["servers", address] => match find(address) {
+            Some(status) => (200, server(status, now)),
+            None => not_found(address),
+        },
+        ["servers", address, "players"] => match find(address) {
+            Some(status) => {
+                let mut body = freshness(status, now);
+                body["players"] = json!(status.players_info.as_ref().map(|players_info| &players_info.players));
+                (200, body)
+            }
+            None => not_found(address),
+        },
+        ["servers", address, "rules"] => match find(address) {
+            Some(status) => {
+                let mut body = freshness(status, now);
+                body["rules"] = json!(status.rules_info.as_ref().map(|rules_info| &rules_info.rules));
+                body["rules_error"] = json!(status.rules_error);
+                (200, body)
+            }
+            None => not_found(address),
+        },
+        _ => (404, json!({ "error": "Not found, try /servers" })),
+    }
+}
+
+/// Every value of `key` in a query string, `+` and `%XX` escapes are decoded.
+fn query_values(query: &str, key: &str) -> Vec<String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(name), Some(value)) if name == key => Some(decode_component(value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn decode_component(value: &str) -> String {
+    percent_decode(&value.replace('+', " "))
+}
+
+/// Decodes the `%XX` escapes of a path segment or query value.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |i: usize| bytes.get(i).and_then(|byte| (*byte as char).to_digit(16));
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (bytes[i], hex(i + 1), hex(i + 2)) {
+            (b'%', Some(high), Some(low)) => {
+                decoded.push((high * 16 + low) as u8);
+                i += 2;
+            }
+            (byte, _, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn not_found(address: &str) -> (u16, Value) {
+    (404, json!({ "error": format!("{} is not a monitored server", address) }))
+}
+
+/// The fields that tell how old the cached data of a server is.
+fn freshness(status: &ServerStatus, now: DateTime<Utc>) -> Value {
+    json!({
+        "address": status.address,
+        "up": status.up,
+        "updated_at": status.updated_at.map(|updated_at| updated_at.to_rfc3339()),
+        "age_seconds": status.age(now),
+        "last_error": status.last_error,
+    })
+}
+
+fn server(status: &ServerStatus, now: DateTime<Utc>) -> Value {
+    let mut body = freshness(status, now);
+    body["ping_ms"] = json!(status.ping.map(|ping| ping.num_milliseconds()));
+    body["info"] = json!(status.server_info);
+    body["tags"] = json!(status.server_info.as_ref().map(Tags::from_server_info));
+    body["steam_id"] = json!(status.server_info.as_ref().and_then(|server_info| server_info.steam_id).map(|steam_id| json!({
+        "steam2": steam_id.steam2(),
+        "steam3": steam_id.steam3(),
+        "persistent": steam_id.is_persistent(),
+    })));
+    body
+}
+
+/// Serves the JSON API on `listen` until the process is stopped.
+pub fn serve(listen: &str, statuses: Statuses) -> Result<(), String> {
+    let server = tiny_http::Server::http(listen)
+        .map_err(|err| format!("Failed to listen on {}, error: {}", listen, err))?;
+    let headers = [
+        "Content-Type: application/json",
+        "Access-Control-Allow-Origin: *",
+    ];
+
+    for request in server.incoming_requests() {
+        let (status_code, body) = route(request.url(), &statuses.read().expect("Server statuses lock is poisoned"), Utc::now());
+        let mut response = tiny_http::Response::from_string(body.to_string()).with_status_code(status_code);
+
+        for header in headers.iter() {
+            response.add_header(header.parse::<tiny_http::Header>().expect("Invalid header"));
+        }
+
+        if let Err(err) = request.respond(response) {
+            eprintln!("Failed to respond to an api request, error: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fixtures::server_info;
+    use crate::server::{Rule, RulesResponse, ServerInfo};
+    use crate::steamid::AccountType;
+    use std::collections::BTreeMap;
+
+    fn statuses(now: DateTime<Utc>) -> Vec<ServerStatus> {
+        vec![ServerStatus {
+            address: "127.0.0.1:27015".to_string(),
+            up: true,
+            server_info: None,
+            players_info: None,
+            rules_info: Some(RulesResponse {
+                header: 0x45,
+                rules: vec![Rule { name: "mp_timelimit".to_string(), value: "30".to_string() }],
+            }),
+            rules_error: None,
+            ping: Some(chrono::Duration::milliseconds(25)),
+            updated_at: Some(now - chrono::Duration::seconds(4)),
+            last_error: None,
+            failures: BTreeMap::new(),
+        }]
+    }
+
+    #[test]
+    fn test_servers_shows_age() {
+        let now = Utc::now();
+        let (status, body) = route("/servers", &statuses(now), now);
+
+        assert_eq!(200, status);
+        assert_eq!(json!(4), body[0]["age_seconds"]);
+        assert_eq!(json!(25), body[0]["ping_ms"]);
+    }
+
+    #[test]
+    fn test_rules_of_a_server() {
+        let now = Utc::now();
+        let (status, body) = route("/servers/127.0.0.1:27015/rules/", &statuses(now), now);
+
+        assert_eq!(200, status);
+        assert_eq!(json!([{ "name": "mp_timelimit", "value": "30" }]), body["rules"]);
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let now = Utc::now();
+
+        assert_eq!(json!([]), route("/servers?tag=cp", &statuses(now), now).1);
+        assert_eq!(1, route("/servers?foo=bar", &statuses(now), now).1.as_array().map_or(0, |servers| servers.len()));
+        assert_eq!(vec!["a b", "ä", "%zz"], query_values("tag=a+b&x=1&tag=%C3%A4&tag=%zz", "tag"));
+    }
+
+    #[test]
+    fn test_steam_id_filter() {
+        let now = Utc::now();
+
+        assert_eq!(json!([]), route("/servers?steam_id=%5BG:1:123456%5D", &statuses(now), now).1);
+        assert_eq!(400, route("/servers?steam_id=gabe", &statuses(now), now).0);
+
+        // Servers report instance 1, the SteamID3 form leaves it out.
+        let mut statuses = statuses(now);
+        statuses[0].server_info = Some(ServerInfo {
+            steam_id: Some(SteamId::new(1, AccountType::GameServer, 1, 123456)),
+            ..server_info("de_dust2", 0)
+        });
+        let (status, body) = route("/servers?steam_id=%5BG:1:123456%5D", &statuses, now);
+
+        assert_eq!(200, status);
+        assert_eq!(json!("127.0.0.1:27015"), body[0]["address"]);
+    }
+
+    #[test]
+    fn test_escaped_address() {
+        let now = Utc::now();
+        let mut statuses = statuses(now);
+        statuses[0].address = "[::1]:27015".to_string();
+
+        assert_eq!(200, route("/servers/[::1]:27015", &statuses, now).0);
+        assert_eq!(200, route("/servers/%5B%3A%3A1%5D%3A27015/rules", &statuses, now).0);
+    }
+
+    #[test]
+    fn test_unknown_paths() {
+        let now = Utc::now();
+
+        assert_eq!(404, route("/servers/10.0.0.1:27015/players", &statuses(now), now).0);
+        assert_eq!(404, route("/", &statuses(now), now).0);
+    }
+}
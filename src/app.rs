@@ -0,0 +1,939 @@
+use crate::bytereader::StringDecoding;
+use crate::diff::{diff_players, PlayerEvent};
+use crate::favorites::Favorites;
+use crate::history::{History, Sample, TimeWindow};
+use crate::keys::KeyBindings;
+use crate::output::format_duration;
+use crate::rules::{self, RuleRow};
+use crate::search::Search;
+use crate::server::{AddressFamily, Server, Response, ServerInfo, Player, PlayersResponse, RulesResponse, SortColumn };
+use crate::store::Store;
+use crate::theme::Theme;
+use crate::util::TabsState;
+use crate::util::event::{Event, Reply, Request, Worker};
+use crate::watcher::{ServerEvent, ServerWatcher};
+use crate::watchlist::{Alert, Watchlist, WatchlistConfig};
+use chrono::{DateTime, Local, Utc};
+use std::collections::{HashSet, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use termion::event::Key;
+use tui::layout::Rect;
+use tui::widgets::TableState;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PromptKind {
+    AddServer,
+    Rename,
+    /// Filters the players of every server as the text is typed.
+    Search { regex: bool },
+}
+
+impl PromptKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PromptKind::AddServer               => "Add server (address [nickname])",
+            PromptKind::Rename                  => "Nickname",
+            PromptKind::Search { regex: false } => "Search (ctrl-r regex)",
+            PromptKind::Search { regex: true }  => "Regex search (ctrl-r substring)",
+        }
+    }
+}
+
+/// A line of text being typed in the status bar.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
+/// How many entries each server keeps in its activity log.
+const LOG_SIZE: usize = 200;
+
+/// Rows skipped by page up and page down.
+const PAGE_SIZE: usize = 10;
+
+/// Rows skipped by a turn of the mouse wheel.
+const SCROLL_SIZE: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Movement {
+    Next,
+    Previous,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    ScrollDown,
+    ScrollUp,
+    /// Selects the row with the given index.
+    To(usize),
+}
+
+impl Movement {
+    /// The row selected after moving from `selected` in a table of `count` rows. Next and
+    /// previous wrap around, the others stop at the first and last row.
+    fn apply(self, selected: Option<usize>, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        let last = count - 1;
+        let row = match (self, selected) {
+            (Movement::Next, Some(i)) if i < last => i + 1,
+            (Movement::Next, _) => 0,
+            (Movement::Previous, Some(i)) if i > 0 && i <= last => i - 1,
+            (Movement::Previous, _) => last,
+            (Movement::PageDown, selected) => selected.map_or(0, |i| i + PAGE_SIZE).min(last),
+            (Movement::PageUp, selected) => selected.map_or(0, |i| i.saturating_sub(PAGE_SIZE)).min(last),
+            (Movement::First, _) => 0,
+            (Movement::Last, _) => last,
+            (Movement::ScrollDown, selected) => selected.map_or(0, |i| i + SCROLL_SIZE).min(last),
+            (Movement::ScrollUp, selected) => selected.map_or(0, |i| i.saturating_sub(SCROLL_SIZE)).min(last),
+            (Movement::To(i), _) if i <= last => i,
+            (Movement::To(_), selected) => return selected,
+        };
+
+        Some(row)
+    }
+}
+
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+/// Where the last frame drew the parts that react to clicks, filled in by `ui::draw`.
+#[derive(Debug, Default, Clone)]
+pub struct ClickAreas {
+    /// The first and last column of every tab title, on the `tabs_row` line.
+    pub tabs: Vec<(u16, u16)>,
+    pub tabs_row: u16,
+    /// The first and last column of every header of the player table, on the `header_row` line.
+    pub header: Vec<(SortColumn, u16, u16)>,
+    pub header_row: u16,
+    /// Where the rows of the player or rules table are and the index of the first one shown.
+    pub rows: Rect,
+    pub first_row: usize,
+}
+
+/// State of the interactive view, one tab with its own table per watched server.
+pub struct App {
+    pub tabs: TabsState,
+    pub tables: Vec<StatefulTable>,
+    pub favorites: Favorites,
+    pub prompt: Option<Prompt>,
+    pub message: Option<String>,
+    pub show_chart: bool,
+    pub show_log: bool,
+    pub show_info: bool,
+    /// Shows the error log instead of the players.
+    pub show_errors: bool,
+    /// Failed queries of every server and other errors, newest last.
+    pub errors: VecDeque<LogEntry>,
+    /// Shows the rules of the current server instead of its players.
+    pub show_rules: bool,
+    pub time_window: TimeWindow,
+    /// File the player events of every server are appended to.
+    pub event_log: Option<PathBuf>,
+    pub watchlist: Watchlist,
+    pub keys: KeyBindings,
+    pub theme: Theme,
+    pub click_areas: ClickAreas,
+    /// Player filter of every table, kept until it is cleared.
+    pub search: Option<Search>,
+    /// History database every update is recorded in.
+    pub store: Option<Store>,
+    timeout: chrono::Duration,
+    family: AddressFamily,
+    decoding: StringDecoding,
+    sort: SortColumn,
+    /// Where the query workers of the tables send their replies.
+    events: mpsc::Sender<Event<Key>>,
+    next_worker_id: usize,
+}
+
+impl App {
+    /// Opens a tab per address, addresses that cannot be resolved end up in the error log.
+    /// Fails only when none of them can be opened.
+    pub fn new(addresses: &[String], favorites: Favorites, timeout: chrono::Duration, family: AddressFamily, decoding: StringDecoding, sort: SortColumn, events: mpsc::Sender<Event<Key>>) -> Result<App, String> {
+        let mut app = App {
+            tabs: TabsState::new(vec![]),
+            tables: vec![],
+            favorites,
+            prompt: None,
+            message: None,
+            show_chart: false,
+            show_log: false,
+            show_info: false,
+            show_errors: false,
+            errors: VecDeque::new(),
+            show_rules: false,
+            time_window: TimeWindow::FiveMinutes,
+            event_log: None,
+            watchlist: Watchlist::new(WatchlistConfig::default(), &[]),
+            keys: KeyBindings::default(),
+            theme: Theme::default(),
+            click_areas: ClickAreas::default(),
+            search: None,
+            store: None,
+            timeout,
+            family,
+            decoding,
+            sort,
+            events,
+            next_worker_id: 0,
+        };
+
+        let mut errors = vec![];
+
+        for address in addresses {
+            if let Err(err) = app.open_tab(address) {
+                app.report_error(Some(address), err.clone());
+                errors.push(err);
+            }
+        }
+
+        if app.tables.is_empty() {
+            return Err(errors.join("\n"));
+        }
+
+        app.tabs.index = 0;
+        Ok(app)
+    }
+
+    pub fn table(&mut self) -> &mut StatefulTable {
+        &mut self.tables[self.tabs.index]
+    }
+
+    pub fn next_tab(&mut self) {
+        self.tabs.next();
+        self.table().changed = false;
+    }
+
+    pub fn previous_tab(&mut self) {
+        self.tabs.previous();
+        self.table().changed = false;
+    }
+
+    pub fn select_tab(&mut self, index: usize) {
+        if index < self.tables.len() {
+            self.tabs.index = index;
+            self.table().changed = false;
+        }
+    }
+
+    pub fn toggle_chart(&mut self) {
+        self.show_chart = !self.show_chart;
+    }
+
+    pub fn toggle_log(&mut self) {
+        self.show_log = !self.show_log;
+    }
+
+    pub fn toggle_info(&mut self) {
+        self.show_info = !self.show_info;
+    }
+
+    pub fn toggle_errors(&mut self) {
+        self.show_errors = !self.show_errors;
+    }
+
+    /// Shows an error in the status bar and keeps it in the error log.
+    pub fn report_error(&mut self, address: Option<&str>, err: String) {
+        if self.errors.len() >= LOG_SIZE {
+            self.errors.pop_front();
+        }
+
+        let message = match address {
+            Some(address) => format!("{}: {}", address, err),
+            None => err,
+        };
+        self.errors.push_back(LogEntry { time: Local::now(), message: message.clone() });
+        self.message = Some(message);
+    }
+
+    pub fn toggle_rules(&mut self) {
+        self.show_rules = !self.show_rules;
+
+        if self.show_rules {
+            self.table().refresh_rules();
+        }
+    }
+
+    /// Moves the selection of the player or rules table, whichever is shown.
+    pub fn move_row(&mut self, movement: Movement) {
+        let show_rules = self.show_rules;
+        let table = self.table();
+
+        if show_rules { table.move_rule(movement) } else { table.move_player(movement) }
+    }
+
+    /// Selects the tab, row or sort column under a left click, `x` and `y` start at 0.
+    pub fn click(&mut self, x: u16, y: u16) {
+        let areas = self.click_areas.clone();
+
+        if y == areas.tabs_row {
+            if let Some(index) = areas.tabs.iter().position(|(start, end)| (*start..=*end).contains(&x)) {
+                self.select_tab(index);
+            }
+        } else if y == areas.header_row {
+            if let Some((column, _, _)) = areas.header.iter().find(|(_, start, end)| (*start..=*end).contains(&x)) {
+                self.table().sort_by_column(*column);
+            }
+        } else if areas.rows.height > 0
+            && (areas.rows.top()..areas.rows.bottom()).contains(&y)
+            && (areas.rows.left()..areas.rows.right()).contains(&x) {
+            self.move_row(Movement::To(areas.first_row + (y - areas.rows.top()) as usize));
+        }
+    }
+
+    pub fn next_time_window(&mut self) {
+        self.time_window = self.time_window.next();
+    }
+
+    /// Asks the query workers to refresh every server, the replies come in as `Event::Reply`.
+    pub fn tick(&mut self) {
+        for table in self.tables.iter_mut() {
+            table.refresh();
+        }
+
+        // Rules are only queried while they are looked at, many servers rate limit them.
+        if self.show_rules {
+            self.table().refresh_rules();
+        }
+    }
+
+    /// Applies the reply of a query worker, replies for closed tabs are dropped.
+    pub fn handle_reply(&mut self, worker_id: usize, reply: Reply) {
+        let index = match self.tables.iter().position(|table| table.worker.id == worker_id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        match reply {
+            Reply::Stats(result) => self.apply_stats(index, *result),
+            Reply::Rules(result) => self.tables[index].apply_rules(result),
+        }
+    }
+
+    fn apply_stats(&mut self, index: usize, result: Result<(ServerInfo, PlayersResponse, chrono::Duration), String>) {
+        let mut events = vec![];
+        let mut alerts = vec![];
+        let mut errors = vec![];
+        let table = &mut self.tables[index];
+
+        // A server that keeps failing the same way is only logged once, it is retried every tick.
+        if let Err(err) = &result {
+            if table.error.as_ref() != Some(err) {
+                errors.push(err.clone());
+            }
+        }
+        let failed = result.as_ref().err().cloned();
+        let title = &self.tabs.titles[index];
+
+        let (player_events, server_events) = table.update(result, self.watchlist.player_thresholds());
+
+        for event in player_events {
+            events.push((table.address.clone(), event.to_string()));
+        }
+
+        for event in server_events.iter() {
+            if self.watchlist.alerts_server_events() {
+                alerts.push(Alert { player: String::new(), event: Some(event.to_string()), server: title.clone(), address: table.address.clone() });
+            }
+
+            events.push((table.address.clone(), event.to_string()));
+        }
+
+        if let Some(store) = self.store.as_mut() {
+            // A failed poll keeps the stale data in the table, only the error is recorded.
+            let recorded = match &failed {
+                None => table.record(store, &server_events),
+                Some(err) => {
+                    let now = Utc::now();
+                    store.record_error(&table.address, now, err)
+                        .and_then(|()| store.record_events(&table.address, now, &server_events))
+                }
+            };
+
+            if let Err(err) = recorded.and_then(|()| store.prune_if_due(Utc::now())) {
+                errors.push(err);
+            }
+        }
+
+        for player in table.update_watched(&self.watchlist) {
+            alerts.push(Alert { player, event: None, server: title.clone(), address: table.address.clone() });
+        }
+
+        let address = table.address.clone();
+
+        if index == self.tabs.index {
+            self.table().changed = false;
+        }
+
+        if let Err(err) = self.write_event_log(&events) {
+            errors.push(err);
+        }
+
+        for alert in alerts {
+            match self.watchlist.notify(&alert) {
+                Ok(()) => self.message = Some(alert.message()),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        for err in errors {
+            self.report_error(Some(&address), err);
+        }
+    }
+
+    fn write_event_log(&self, events: &[(String, String)]) -> Result<(), String> {
+        let path = match &self.event_log {
+            Some(path) if !events.is_empty() => path,
+            _ => return Ok(()),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("Failed to open {}, error: {}", path.display(), err))?;
+        let now = Local::now().to_rfc3339();
+
+        for (address, event) in events {
+            writeln!(file, "{} {} {}", now, address, event)
+                .map_err(|err| format!("Failed to write {}, error: {}", path.display(), err))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn start_prompt(&mut self, kind: PromptKind) {
+        let text = match kind {
+            PromptKind::AddServer => String::new(),
+            PromptKind::Rename => {
+                let address = self.table().address.clone();
+                self.favorites.find(&address).and_then(|favorite| favorite.nickname.clone()).unwrap_or_default()
+            }
+            PromptKind::Search { .. } => String::new(),
+        };
+
+        self.prompt = Some(match (kind, &self.search) {
+            (PromptKind::Search { .. }, Some(search)) => Prompt { kind: PromptKind::Search { regex: search.regex }, text: search.text.clone() },
+            _ => Prompt { kind, text },
+        });
+    }
+
+    /// Edits the open prompt, Enter submits it and Esc cancels it. A search is applied
+    /// while typing, Enter keeps it and Esc clears it.
+    pub fn prompt_input(&mut self, key: Key) {
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+
+        match key {
+            Key::Char('\n') => self.submit_prompt(),
+            Key::Esc => {
+                if let PromptKind::Search { .. } = prompt.kind {
+                    self.set_search(None);
+                }
+                self.prompt = None;
+            }
+            Key::Backspace => {
+                prompt.text.pop();
+                self.update_search();
+            }
+            Key::Ctrl('r') => {
+                if let PromptKind::Search { regex } = prompt.kind {
+                    prompt.kind = PromptKind::Search { regex: !regex };
+                    self.update_search();
+                }
+            }
+            Key::Char(c) => {
+                prompt.text.push(c);
+                self.update_search();
+            }
+            _ => {}
+        }
+    }
+
+    fn submit_prompt(&mut self) {
+        if let Some(prompt) = self.prompt.take() {
+            match prompt.kind {
+                PromptKind::AddServer => self.add_server(prompt.text.trim()),
+                PromptKind::Rename => self.rename_current(prompt.text.trim()),
+                PromptKind::Search { .. } => {}
+            }
+        }
+    }
+
+    /// Filters the tables with the text of an open search prompt, an invalid regex
+    /// keeps the previous filter.
+    fn update_search(&mut self) {
+        let (text, regex) = match &self.prompt {
+            Some(Prompt { kind: PromptKind::Search { regex }, text }) => (text.clone(), *regex),
+            _ => return,
+        };
+
+        if text.is_empty() {
+            self.set_search(None);
+            return;
+        }
+
+        match Search::new(&text, regex) {
+            Ok(search) => {
+                self.message = None;
+                self.set_search(Some(search));
+            }
+            Err(err) => self.message = Some(err),
+        }
+    }
+
+    fn set_search(&mut self, search: Option<Search>) {
+        for table in self.tables.iter_mut() {
+            table.set_search(search.clone());
+        }
+
+        self.search = search;
+    }
+
+    /// Adds `address [nickname]` to the favorites and opens a tab for it.
+    pub fn add_server(&mut self, input: &str) {
+        let mut parts = input.splitn(2, char::is_whitespace);
+        let address = match parts.next() {
+            Some(address) if !address.is_empty() => self.favorites.resolve(address),
+            _ => return,
+        };
+        let nickname = parts.next().map(|nickname| nickname.trim().to_string()).filter(|nickname| !nickname.is_empty());
+
+        self.favorites.add(&address, nickname);
+        self.save_favorites(format!("Added {} to the favorites", address));
+
+        match self.tables.iter().position(|table| table.address == address) {
+            Some(index) => {
+                self.tabs.titles[index] = self.title(&address);
+                self.select_tab(index);
+            }
+            None => {
+                if let Err(err) = self.open_tab(&address) {
+                    self.report_error(Some(&address), err);
+                }
+            }
+        }
+    }
+
+    /// Sets the nickname of the current server, adding it to the favorites when needed.
+    pub fn rename_current(&mut self, nickname: &str) {
+        let address = self.table().address.clone();
+
+        self.favorites.add(&address, None);
+        self.favorites.rename(&address, nickname);
+        self.save_favorites(format!("Renamed {}", address));
+        self.tabs.titles[self.tabs.index] = self.title(&address);
+    }
+
+    /// Removes the current server from the favorites and closes its tab, unless it is the last one.
+    pub fn remove_current(&mut self) {
+        let address = self.table().address.clone();
+
+        if self.favorites.remove(&address).is_some() {
+            self.save_favorites(format!("Removed {} from the favorites", address));
+        }
+
+        if self.tables.len() > 1 {
+            self.tables.remove(self.tabs.index);
+            self.tabs.titles.remove(self.tabs.index);
+            self.tabs.index = self.tabs.index.min(self.tables.len() - 1);
+        } else {
+            self.tabs.titles[0] = address;
+        }
+    }
+
+    fn open_tab(&mut self, address: &str) -> Result<(), String> {
+        let mut server = match Server::connect(address, self.family, self.timeout) {
+            Response::Ok(server) => server,
+            Response::Error(err) => return Err(err.to_string()),
+        };
+        server.set_decoding(self.decoding);
+
+        let worker = Worker::spawn(self.next_worker_id, server, self.events.clone());
+        self.next_worker_id += 1;
+
+        let mut table = StatefulTable::new(address, worker, self.sort);
+        table.set_search(self.search.clone());
+        table.refresh();
+
+        self.tables.push(table);
+        self.tabs.titles.push(self.title(address));
+        self.tabs.index = self.tables.len() - 1;
+
+        Ok(())
+    }
+
+    fn title(&self, address: &str) -> String {
+        match self.favorites.find(address) {
+            Some(favorite) => favorite.title().to_string(),
+            None => address.to_string(),
+        }
+    }
+
+    fn save_favorites(&mut self, message: String) {
+        self.message = Some(match self.favorites.save() {
+            Ok(()) => message,
+            Err(err) => err,
+        });
+    }
+}
+
+pub struct StatefulTable{
+    pub address: String,
+    pub worker: Worker,
+    /// A stats request is running on the worker.
+    pub refreshing: bool,
+    pub rules_refreshing: bool,
+    pub server_info: Option<ServerInfo>,
+    pub players_info: Option<PlayersResponse>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub ping: Option<chrono::Duration>,
+    pub history: History,
+    watcher: ServerWatcher,
+    pub log: VecDeque<LogEntry>,
+    /// Watched players that were online at the last update.
+    pub watched_online: HashSet<String>,
+    sort: SortColumn,
+    sort_reversed: bool,
+    search: Option<Search>,
+    pub rules: Option<RulesResponse>,
+    pub rules_error: Option<String>,
+    /// Error of the last query, the previous data is kept and shown as stale.
+    pub error: Option<String>,
+    changed_rules: HashSet<String>,
+    pub rules_state: TableState,
+    pub state: TableState,
+    /// The first player and rule row shown, the tables are scrolled by `ui::draw`.
+    pub first_row: usize,
+    pub first_rule: usize,
+    pub items: Vec<Vec<String>>,
+    /// The players behind `items`, in the same order.
+    row_players: Vec<Player>,
+    /// Set when an update changed anything worth looking at, cleared when the tab is viewed.
+    pub changed: bool,
+}
+
+impl StatefulTable {
+    pub fn new(address: &str, worker: Worker, sort: SortColumn) -> StatefulTable {
+        StatefulTable {
+            address: address.to_string(),
+            server_info: None,
+            players_info: None,
+            updated_at: None,
+            ping: None,
+            history: History::new(),
+            watcher: ServerWatcher::new(),
+            log: VecDeque::new(),
+            watched_online: HashSet::new(),
+            worker,
+            refreshing: false,
+            rules_refreshing: false,
+            sort,
+            sort_reversed: false,
+            search: None,
+            rules: None,
+            rules_error: None,
+            error: None,
+            changed_rules: HashSet::new(),
+            rules_state: TableState::default(),
+            state: TableState::default(),
+            first_row: 0,
+            first_rule: 0,
+            items: vec![],
+            row_players: vec![],
+            changed: false,
+        }
+    }
+
+    /// Asks the worker for the server info and players, unless a request is still running.
+    pub fn refresh(&mut self) {
+        if !self.refreshing {
+            self.refreshing = true;
+            self.worker.send(Request::Stats);
+        }
+    }
+
+    /// Asks the worker for the rules, unless a request is still running.
+    pub fn refresh_rules(&mut self) {
+        if !self.rules_refreshing && self.answers_rules() {
+            self.rules_refreshing = true;
+            self.worker.send(Request::Rules);
+        }
+    }
+
+    /// Whether the server is worth a rules query, servers of some games never answer.
+    pub fn answers_rules(&self) -> bool {
+        self.server_info.as_ref().is_none_or(ServerInfo::answers_rules)
+    }
+
+    /// Records the latest update and its server events in the history database.
+    pub fn record(&self, store: &mut Store, server_events: &[ServerEvent]) -> Result<(), String> {
+        if let (Some(server_info), Some(players_info), Some(updated_at), Some(ping)) = (&self.server_info, &self.players_info, self.updated_at, self.ping) {
+            store.record_poll(&self.address, updated_at, server_info, players_info, ping)?;
+        }
+
+        store.record_events(&self.address, Utc::now(), server_events)
+    }
+
+    /// Applies a reply of the worker and returns what happened to its players and the server
+    /// itself since the last update, `thresholds` are the player counts worth an event.
+    pub fn update(&mut self, result: Result<(ServerInfo, PlayersResponse, chrono::Duration), String>, thresholds: &[u8]) -> (Vec<PlayerEvent>, Vec<ServerEvent>) {
+        self.refreshing = false;
+
+        let (server_info, players_info, ping) = match result {
+            Ok(stats) => stats,
+            Err(err) => {
+                self.history.push(Sample::failed(Utc::now()));
+                let server_events = self.watcher.failed(&err);
+                self.error = Some(err);
+                for event in server_events.iter() {
+                    self.push_log(event.to_string());
+                    self.changed = true;
+                }
+
+                return (vec![], server_events);
+            }
+        };
+        self.error = None;
+        let now = Utc::now();
+
+        let events = match (&self.players_info, self.updated_at) {
+            (Some(previous), Some(updated_at)) => diff_players(&previous.players, &players_info.players, now - updated_at),
+            _ => vec![],
+        };
+        let server_events = self.watcher.update(&server_info, thresholds);
+
+        for event in events.iter() {
+            self.push_log(event.to_string());
+        }
+
+        for event in server_events.iter() {
+            self.push_log(event.to_string());
+        }
+
+        self.history.push(Sample {
+            time: now,
+            players: Some(server_info.players),
+            max_players: Some(server_info.max_players),
+            ping: Some(ping),
+        });
+
+        let server_changed = match &self.server_info {
+            Some(previous) => previous.name != server_info.name
+                || previous.map != server_info.map
+                || previous.players != server_info.players,
+            None => false,
+        };
+        let had_server_info = self.server_info.is_some();
+        let previous_rows = self.items.clone();
+
+        self.server_info = Some(server_info);
+        self.players_info = Some(players_info);
+        self.updated_at = Some(now);
+        self.ping = Some(ping);
+        self.sort_rows();
+
+        if server_changed || !events.is_empty() || !server_events.is_empty() || (had_server_info && self.items != previous_rows) {
+            self.changed = true;
+        }
+
+        (events, server_events)
+    }
+
+    /// The current sort column and whether it goes against its natural direction.
+    pub fn sort(&self) -> (SortColumn, bool) {
+        (self.sort, self.sort_reversed)
+    }
+
+    /// The columns of the player table.
+    pub fn columns(&self) -> Vec<SortColumn> {
+        match &self.players_info {
+            Some(players_info) => players_info.columns(),
+            None => PlayersResponse { header: 0, players: vec![], is_ship: false }.columns(),
+        }
+    }
+
+    /// Sorts by the next column in its natural direction.
+    pub fn next_sort_column(&mut self) {
+        self.sort = self.sort.next(&self.columns());
+        self.sort_reversed = false;
+        self.sort_rows();
+    }
+
+    /// Sorts by `column`, or reverses the sort when it already is the sort column.
+    pub fn sort_by_column(&mut self, column: SortColumn) {
+        if column == self.sort {
+            self.reverse_sort();
+        } else {
+            self.sort = column;
+            self.sort_reversed = false;
+            self.sort_rows();
+        }
+    }
+
+    pub fn reverse_sort(&mut self) {
+        self.sort_reversed = !self.sort_reversed;
+        self.sort_rows();
+    }
+
+    /// Applies the rules fetched by the worker and marks the ones that changed since the last fetch.
+    pub fn apply_rules(&mut self, result: Result<RulesResponse, String>) {
+        self.rules_refreshing = false;
+
+        match result {
+            Ok(rules) => {
+                self.changed_rules = match &self.rules {
+                    Some(previous) => rules::changed_rules(previous, &rules),
+                    None => HashSet::new(),
+                };
+                self.rules = Some(rules);
+                self.rules_error = None;
+            }
+            Err(err) => self.rules_error = Some(err),
+        }
+    }
+
+    /// The rules to show, pinned ones first and filtered by the search.
+    pub fn rule_rows(&self) -> Vec<RuleRow> {
+        match &self.rules {
+            Some(rules) => rules::rule_rows(rules, &self.changed_rules, self.search.as_ref()),
+            None => vec![],
+        }
+    }
+
+    pub fn move_rule(&mut self, movement: Movement) {
+        let count = self.rule_rows().len();
+        self.rules_state.select(movement.apply(self.rules_state.selected(), count));
+    }
+
+    /// Only shows the players matching `search`.
+    pub fn set_search(&mut self, search: Option<Search>) {
+        self.search = search;
+        self.sort_rows();
+    }
+
+    /// Sorts and filters the players and rebuilds the rows, the selection stays on the same player.
+    fn sort_rows(&mut self) {
+        let selected = self.state.selected()
+            .and_then(|index| self.row_players.get(index))
+            .cloned();
+        let columns = self.columns();
+
+        let players_info = match self.players_info.as_mut() {
+            Some(players_info) => players_info,
+            None => return,
+        };
+        players_info.sort_by(self.sort, self.sort_reversed);
+
+        let search = &self.search;
+        self.row_players = players_info.players
+            .iter()
+            .filter(|player| search.as_ref().is_none_or(|search| search.matches(&player.name)))
+            .cloned()
+            .collect();
+        self.items = self.row_players
+            .iter()
+            .map(|player| columns.iter().map(|column| match column {
+                SortColumn::Name     => player.name.clone(),
+                SortColumn::Score    => player.score.to_string(),
+                SortColumn::Duration => format_duration(&player.duration),
+                SortColumn::Deaths   => player.deaths.to_string(),
+                SortColumn::Money    => player.money.map(|money| money.to_string()).unwrap_or_default(),
+            }).collect())
+            .collect();
+
+        let index = match selected {
+            Some(player) => find_player(&self.row_players, &player).or(self.state.selected()),
+            None => self.state.selected(),
+        };
+        let last = self.items.len().checked_sub(1);
+        self.state.select(index.and_then(|index| last.map(|last| index.min(last))));
+    }
+
+    /// Returns the watched players that are online now but were not at the last update.
+    pub fn update_watched(&mut self, watchlist: &Watchlist) -> Vec<String> {
+        let online: HashSet<String> = match &self.players_info {
+            Some(players_info) => players_info.players
+                .iter()
+                .filter(|player| watchlist.matches(&player.name))
+                .map(|player| player.name.clone())
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        let mut appeared: Vec<String> = online.difference(&self.watched_online).cloned().collect();
+        appeared.sort();
+
+        for player in appeared.iter() {
+            self.push_log(format!("{} is online (watchlist)", player));
+            self.changed = true;
+        }
+
+        self.watched_online = online;
+        appeared
+    }
+
+    pub fn push_log(&mut self, message: String) {
+        if self.log.len() >= LOG_SIZE {
+            self.log.pop_front();
+        }
+
+        self.log.push_back(LogEntry { time: Local::now(), message });
+    }
+
+    pub fn move_player(&mut self, movement: Movement) {
+        self.state.select(movement.apply(self.state.selected(), self.items.len()));
+    }
+}
+
+/// Finds `player` again after a sort or an update. Names are not unique, so the player
+/// must also have the same connection time, or a longer one when the list was polled again.
+fn find_player(players: &[Player], player: &Player) -> Option<usize> {
+    players
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| other.name == player.name && other.duration >= player.duration)
+        .min_by_key(|(_, other)| other.duration - player.duration)
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fixtures;
+
+    #[test]
+    fn test_movement() {
+        assert_eq!(Some(0), Movement::Next.apply(Some(4), 5));
+        assert_eq!(Some(4), Movement::Previous.apply(None, 5));
+        assert_eq!(Some(12), Movement::PageDown.apply(Some(2), 20));
+        assert_eq!(Some(19), Movement::PageDown.apply(Some(15), 20));
+        assert_eq!(Some(0), Movement::PageUp.apply(Some(5), 20));
+        assert_eq!(Some(19), Movement::Last.apply(None, 20));
+        assert_eq!(None, Movement::Next.apply(None, 0));
+        assert_eq!(Some(3), Movement::ScrollDown.apply(None, 20).and_then(|i| Movement::ScrollDown.apply(Some(i), 20)));
+        assert_eq!(Some(7), Movement::To(7).apply(Some(2), 20));
+        assert_eq!(Some(2), Movement::To(25).apply(Some(2), 20));
+    }
+
+    #[test]
+    fn test_find_player() {
+        let before = fixtures::players(&[("alice", 5, 60), ("Player", 2, 300), ("Player", 7, 30)]).players;
+        let after = fixtures::players(&[("Player", 7, 40), ("Player", 2, 310), ("bob", 0, 5), ("alice", 6, 70)]).players;
+
+        assert_eq!(Some(3), find_player(&after, &before[0]));
+        assert_eq!(Some(1), find_player(&after, &before[1]));
+        assert_eq!(Some(0), find_player(&after, &before[2]));
+        assert_eq!(None, find_player(&before, &after[2]));
+    }
+}
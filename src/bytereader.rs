@@ -1,11 +1,63 @@
-#[allow(dead_code)]
-
-use std::str;
+use std::str::{self, FromStr};
 use std::convert::TryInto;
 
+/// How the null terminated strings of a response are turned into text.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum StringDecoding {
+    /// Invalid UTF-8 is an error, like any other malformed response.
+    Strict,
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
+    Lossy,
+    /// Valid UTF-8 is kept, anything else is read as Windows-1252, which
+    /// older clients and servers use for names.
+    #[default]
+    Legacy,
+}
+
+impl StringDecoding {
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, String> {
+        match (self, str::from_utf8(bytes)) {
+            (_, Ok(value)) => Ok(value.to_string()),
+            (StringDecoding::Strict, Err(err)) => Err(format!("Invalid UTF-8 in a string, error: {}", err)),
+            (StringDecoding::Lossy, Err(_)) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            (StringDecoding::Legacy, Err(_)) => Ok(bytes.iter().map(|&byte| windows_1252(byte)).collect()),
+        }
+    }
+}
+
+impl FromStr for StringDecoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "strict" => Ok(StringDecoding::Strict),
+            "lossy"  => Ok(StringDecoding::Lossy),
+            "legacy" => Ok(StringDecoding::Legacy),
+            _        => Err(format!("Unknown string decoding '{}', expected strict, lossy or legacy", value)),
+        }
+    }
+}
+
+/// Windows-1252 differs from Latin-1 only in 0x80..=0x9F, the five bytes it
+/// leaves undefined keep their Latin-1 control character.
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '\u{20AC}', '\u{81}',   '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+        '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}',   '\u{017D}', '\u{8F}',
+        '\u{90}',   '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+        '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}',   '\u{017E}', '\u{0178}',
+    ];
+
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        _           => byte as char,
+    }
+}
+
 pub struct ByteReader {
     response: Vec<u8>,
-    pub iterator: usize
+    pub iterator: usize,
+    decoding: StringDecoding,
 }
 
 impl ByteReader {
@@ -13,147 +65,101 @@ impl ByteReader {
         ByteReader {
             response: data,
             iterator: 0, 
+            decoding: StringDecoding::default(),
         }
     }
 
+    pub fn with_decoding(mut self, decoding: StringDecoding) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
     pub fn remaining(&mut self) -> usize {
         self.response.len() - self.iterator
     }
 
-    pub fn get_bytes(&mut self, length: usize) -> &[u8] {
-        self.check_byte_size(length);
-
-        let value = &self.response[self.iterator..self.iterator+length];
-        
-        self.iterator += length;
-        
-        value
+    pub fn get_byte(&mut self) -> Result<u8, String> {
+        self.read().map(u8::from_le_bytes)
     }
 
-    pub fn get_byte(&mut self) -> u8 {
-        let byte_size = 1;
-        
-        self.check_byte_size(byte_size);
+    pub fn get_float(&mut self) -> Result<f32, String> {
+        self.read().map(f32::from_le_bytes)
+    }
 
-        let value = self.response[self.iterator];
-        
-        self.iterator += byte_size;
-        
-        value
+    pub fn get_int(&mut self) -> Result<i32, String> {
+        self.read().map(i32::from_le_bytes)
     }
 
-    pub fn get_float(&mut self) -> f32 {
-        let byte_size = 4;
-        
-        self.check_byte_size(byte_size);
-        
-        let value = f32::from_le_bytes(
-            (&self.response[self.iterator..self.iterator+byte_size])
-                .try_into()
-                .expect("Slice with incorrect length")
-        );
-        
-        self.iterator += byte_size;
-        
-        value
+    pub fn get_long(&mut self) -> Result<u32, String> {
+        self.read().map(u32::from_le_bytes)
     }
 
-    pub fn get_short(&mut self) -> i16 {
-        let byte_size = 2;      
-        
-        self.check_byte_size(byte_size);
-        
-        let value = i16::from_le_bytes(
-            (&self.response[self.iterator..self.iterator+byte_size])
-                .try_into()
-                .expect("Slice with incorrect length")
-        );
-        
-        self.iterator += byte_size;
-        
-        value
+    pub fn get_u16(&mut self) -> Result<u16, String> {
+        self.read().map(u16::from_le_bytes)
     }
 
-    #[ignore = "unused_attributes"]
-    pub fn get_int(&mut self) -> i32 {
-        let byte_size = 4;
-        
-        self.check_byte_size(byte_size);
-        
-        let value = i32::from_le_bytes(
-            (&self.response[self.iterator..self.iterator+byte_size])
-                .try_into()
-                .expect("Slice with incorrect length")
-        );
-        
-        self.iterator += byte_size;
-        
-        value
+    pub fn get_u64(&mut self) -> Result<u64, String> {
+        self.read().map(u64::from_le_bytes)
     }
 
-    pub fn get_long(&mut self) -> u32 {
-        let byte_size = 4;
-        
-        self.check_byte_size(byte_size);
-        
-        let value = u32::from_le_bytes(
-            (&self.response[self.iterator..self.iterator+byte_size])
-                .try_into()
-                .expect("Slice with incorrect length")
-        );
-        
-        self.iterator += byte_size;
-        
-        value
+    pub fn get_string(&mut self) -> Result<String, String> {
+        let decoding = self.decoding;
+
+        decoding.decode(self.get_raw_string()?)
     }
 
-    pub fn get_string(&mut self) -> String {
-        let byte_size = 1;
-        
-        self.check_byte_size(byte_size);
+    /// The bytes of the next null terminated string, without the null char.
+    pub fn get_raw_string(&mut self) -> Result<&[u8], String> {
+        self.check_byte_size(1)?;
         
-        let pos = self.get_next_null_char_pos();                
-        let val = str::from_utf8(&self.response[self.iterator..pos]).unwrap();
+        let pos = self.get_next_null_char_pos()?;
+        let start = self.iterator;
         
         self.iterator = pos + 1;
         
-        String::from(val)
+        Ok(&self.response[start..pos])
     }
 
-    pub fn peek_remaining_bytes(&mut self) -> &[u8] {
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, String> {
+        self.decoding.decode(bytes)
+    }
+
+    pub fn peek_remaining_bytes(&mut self) -> Result<&[u8], String> {
         if self.iterator >= self.response.len() {
-            panic!("out of range")
+            Err("Unexpected end of the response, no bytes remaining".to_string())
         } else {
-            &self.response[self.iterator..]
+            Ok(&self.response[self.iterator..])
         }        
     }
 
-    fn get_next_null_char_pos(&self) -> usize {
-        let data = &self.response[..];
-        let mut found: bool = false;
-        let mut pos: usize = 0;
+    /// The next `N` bytes, for the little endian numbers.
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        self.check_byte_size(N)?;
 
-        for i in self.iterator..data.len() {
-            if data[i] == 0x00 {
-                pos = i;
-                found = true;
-                break;
-            }
-        }
-        
-        if !found {
-            panic!("Didn't find a null char in the remaining bytes");
-        } else {
-            pos
+        let value = self.response[self.iterator..self.iterator+N]
+            .try_into()
+            .expect("Slice with incorrect length");
+
+        self.iterator += N;
+
+        Ok(value)
+    }
+
+    fn get_next_null_char_pos(&self) -> Result<usize, String> {
+        match self.response[self.iterator..].iter().position(|byte| *byte == 0x00) {
+            Some(offset) => Ok(self.iterator + offset),
+            None => Err("Unexpected end of the response, a string has no null char".to_string()),
         }
     }
 
-    fn check_byte_size(&mut self, byte_size: usize) {
+    fn check_byte_size(&mut self, byte_size: usize) -> Result<(), String> {
         let remaining = self.remaining();
         
         if remaining < byte_size {
-            panic!("out of range, tried to access {} bytes, but only {} bytes remaining", byte_size, remaining);
+            return Err(format!("Unexpected end of the response, tried to read {} bytes, but only {} bytes remaining", byte_size, remaining));
         }
+
+        Ok(())
     }
 }
 
@@ -167,66 +173,52 @@ mod tests {
 
         let mut reader = ByteReader::new(data);
 
-        assert_eq!(0x6a, reader.get_byte());
+        assert_eq!(Ok(0x6a), reader.get_byte());
     }
 
     #[test]
-    #[should_panic]
-    fn test_get_byte_out_or_range() {
+    fn test_get_byte_out_of_range() {
         let data = vec![0x6a];
         let mut reader = ByteReader::new(data);
 
-        reader.get_byte();
-        reader.get_byte();
+        assert!(reader.get_byte().is_ok());
+        assert!(reader.get_byte().is_err());
     }
 
     #[test]
-    fn test_get_bytes() {
-        let data = vec![0x6a, 0x6a, 0x6a];
-
-        let mut reader = ByteReader::new(data);
-
-        assert_eq!([0x6a, 0x6a, 0x6a], reader.get_bytes(3));
-    }
-
-    #[test]
-    #[should_panic]
-    fn test_get_bytes_out_of_range() {
-        let data = vec![0x6a, 0x6a, 0x6a];
+    fn test_get_int() {
+        let data = i32::to_le_bytes(655556).to_vec();
 
         let mut reader = ByteReader::new(data);
 
-        reader.get_bytes(4);
+        assert_eq!(Ok(655556), reader.get_int());        
     }
 
     #[test]
-    fn test_get_short() {
-        let data = [i16::to_le_bytes(6230), i16::to_le_bytes(1234)].concat();
-        
-        data.iter().for_each(|b| println!("{}", b));
+    fn test_get_long() {
+        let data = u64::to_le_bytes(655556000).to_vec();
 
         let mut reader = ByteReader::new(data);
 
-        assert_eq!(6230, reader.get_short());
-        assert_eq!(1234, reader.get_short());
+        assert_eq!(Ok(655556000u32), reader.get_long());
     }
 
     #[test]
-    fn test_get_int() {
-        let data = i32::to_le_bytes(655556).to_vec();
+    fn test_get_u16() {
+        let data = u16::to_le_bytes(54321).to_vec();
 
         let mut reader = ByteReader::new(data);
 
-        assert_eq!(655556, reader.get_int());        
+        assert_eq!(Ok(54321), reader.get_u16());
     }
 
     #[test]
-    fn test_get_long() {
-        let data = u64::to_le_bytes(655556000).to_vec();
+    fn test_get_u64() {
+        let data = u64::to_le_bytes(76561198000000000).to_vec();
 
         let mut reader = ByteReader::new(data);
 
-        assert_eq!(655556000u32, reader.get_long());
+        assert_eq!(Ok(76561198000000000), reader.get_u64());
     }
 
     #[test]
@@ -237,7 +229,7 @@ mod tests {
         
         let mut reader = ByteReader::new(data);
 
-        assert_eq!(132.34, reader.get_float());        
+        assert_eq!(Ok(132.34), reader.get_float());        
     }
 
     #[test]
@@ -250,14 +242,35 @@ mod tests {
                 0x67, 0x00, 0x67, 0x6f, 
                 0x72, 0x61, 0x6e, 0x00 ]);
         
-        assert_eq!("jimmy", reader.get_string());
-        assert_eq!("sorting", reader.get_string());
-        assert_eq!("goran", reader.get_string());
+        assert_eq!(Ok("jimmy".to_string()), reader.get_string());
+        assert_eq!(Ok("sorting".to_string()), reader.get_string());
+        assert_eq!(Ok("goran".to_string()), reader.get_string());
+    }
+
+    #[test]
+    fn test_get_string_decodings() {
+        let data = vec![0x4a, 0xf6, 0x72, 0x67, 0x00, 0x93, 0x78, 0x94, 0x00, 0xc3, 0xa9, 0x00];
+
+        let mut reader = ByteReader::new(data.clone());
+        assert_eq!(Ok("Jörg".to_string()), reader.get_string());
+        assert_eq!(Ok("\u{201C}x\u{201D}".to_string()), reader.get_string());
+        assert_eq!(Ok("é".to_string()), reader.get_string());
+
+        let mut reader = ByteReader::new(data).with_decoding(StringDecoding::Lossy);
+        assert_eq!(Ok("J\u{FFFD}rg".to_string()), reader.get_string());
+        assert_eq!(Ok(&[0x93, 0x78, 0x94][..]), reader.get_raw_string());
+        assert_eq!(Ok("é".to_string()), reader.get_string());
+    }
+
+    #[test]
+    fn test_get_string_strict_invalid_utf8() {
+        let mut reader = ByteReader::new(vec![0x4a, 0xf6, 0x72, 0x67, 0x00]).with_decoding(StringDecoding::Strict);
+
+        assert!(reader.get_string().is_err());
     }
 
     #[test]
-    #[should_panic]
-    fn test_get_string_out_of_range_should_panic() {
+    fn test_get_string_out_of_range() {
         let mut reader = ByteReader::new(
             vec![
                 0x6a, 0x69, 0x6d, 0x6d, 
@@ -266,15 +279,14 @@ mod tests {
                 0x67, 0x00, 0x67, 0x6f, 
                 0x72, 0x61, 0x6e, 0x00 ]);
         
-        reader.get_string();
-        reader.get_string();
-        reader.get_string();
-        reader.get_string(); // out of range
+        assert!(reader.get_string().is_ok());
+        assert!(reader.get_string().is_ok());
+        assert!(reader.get_string().is_ok());
+        assert!(reader.get_string().is_err()); // out of range
     }
 
     #[test]
-    #[should_panic]
-    fn test_get_string_with_missing_null_char_should_panic() {
+    fn test_get_string_with_missing_null_char() {
         let mut reader = ByteReader::new(
             vec![
                 0x6a, 0x69, 0x6d, 0x6d, 
@@ -284,10 +296,7 @@ mod tests {
                 0x6f, 0x72, 0x61, 
                 0x6e ]);
         
-        reader.get_string();
-        reader.get_string();
-        reader.get_string();
-        reader.get_string(); // out of range
+        assert!(reader.get_string().is_err());
     }
 
     #[test]
@@ -302,19 +311,18 @@ mod tests {
                 0x72, 0x61, 0x6e, 0x00,
                 0x72, 0x61, 0x6e, 0x00 ]);
         
-        reader.get_string(); // 6
-        reader.get_short(); // 6 + 2 = 8
-        reader.get_int(); // 8 + 4 = 12
-        reader.get_long(); // 12 + 4 = 16
-        reader.get_byte(); // 16 + 1 = 17
-        reader.get_bytes(3); // 17 + 3 = 20 - 8 bytes remaining
+        reader.get_string().unwrap(); // 6
+        reader.get_u16().unwrap(); // 6 + 2 = 8
+        reader.get_int().unwrap(); // 8 + 4 = 12
+        reader.get_long().unwrap(); // 12 + 4 = 16
+        reader.get_byte().unwrap(); // 16 + 1 = 17
+        reader.get_string().unwrap(); // 17 + 3 = 20 - 8 bytes remaining
 
-        assert_eq!([0x72, 0x61, 0x6e, 0x00, 0x72, 0x61, 0x6e, 0x00], reader.peek_remaining_bytes());
+        assert_eq!(Ok(&[0x72, 0x61, 0x6e, 0x00, 0x72, 0x61, 0x6e, 0x00][..]), reader.peek_remaining_bytes());
     }
 
     #[test]
-    #[should_panic]
-    fn test_peek_remaining_bytes_out_of_range_should_panic() {
+    fn test_peek_remaining_bytes_out_of_range() {
         let mut reader = ByteReader::new(
             vec![
                 0x6a, 0x69, 0x6d, 0x6d, 
@@ -327,6 +335,6 @@ mod tests {
         
         reader.iterator = 28;
 
-        reader.peek_remaining_bytes();
+        assert!(reader.peek_remaining_bytes().is_err());
     }
 }
\ No newline at end of file
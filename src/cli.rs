@@ -0,0 +1,422 @@
+use argh::FromArgs;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::api;
+use crate::bytereader::StringDecoding;
+use crate::config::Settings;
+use crate::favorites::Favorites;
+use crate::games::{self, GameRegistry};
+use crate::metrics;
+use crate::output::{self, OutputFormat, Tabular};
+use crate::poller::{self, PollConfig};
+use crate::server::{self, AddressFamily, Response, Server, SortColumn};
+use crate::store::{HistoryConfig, Store};
+
+/// Server shown by the interactive view when no `--address` is given and there are no favorites.
+pub const DEFAULT_ADDRESS: &str = "178.236.67.8:27015";
+
+/// Exit code used when the query itself failed (timeout, bad response, ...).
+pub const EXIT_QUERY_FAILED: i32 = 1;
+
+/// Exit code used when the server address could not be used at all.
+pub const EXIT_CONNECT_FAILED: i32 = 2;
+
+#[derive(FromArgs)]
+/// Query Valve game servers. Starts the interactive view when no subcommand is given.
+pub struct Args {
+    #[argh(option, short = 'a')]
+    /// server address or favorite nickname to watch or export, can be repeated
+    pub address: Vec<String>,
+
+    #[argh(option, short = 'i', default = "1")]
+    /// seconds between refreshes of the interactive view (default 1)
+    pub interval: u64,
+
+    #[argh(option, short = 't', default = "5")]
+    /// seconds to wait for a server response (default 5)
+    pub timeout: u64,
+
+    #[argh(option, short = 's', default = "SortColumn::Score")]
+    /// initial player sort column: name, score (default), duration, deaths or money
+    pub sort: SortColumn,
+
+    #[argh(option, default = "StringDecoding::Legacy")]
+    /// how invalid UTF-8 in names is read: strict, lossy or legacy (default, falls back to Windows-1252)
+    pub decoding: StringDecoding,
+
+    #[argh(option, default = "AddressFamily::Any")]
+    /// addresses of host names to query: any (default), ipv4 or ipv6
+    pub family: AddressFamily,
+
+    #[argh(option, short = 'w')]
+    /// player name to watch for, `*` and `?` are wildcards, can be repeated
+    pub watch: Vec<String>,
+
+    #[argh(option)]
+    /// file to append player joins, leaves, renames and score changes to
+    pub event_log: Option<PathBuf>,
+
+    #[argh(option)]
+    /// sqlite database to record every poll in, overrides the one in the config file
+    pub database: Option<PathBuf>,
+
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// The servers to watch: the given addresses, else all favorites, else the default server.
+    pub fn addresses(&self, favorites: &Favorites) -> Vec<String> {
+        if !self.address.is_empty() {
+            self.address.iter().map(|name| favorites.resolve(name)).collect()
+        } else if !favorites.servers.is_empty() {
+            favorites.servers.iter().map(|favorite| favorite.address.clone()).collect()
+        } else {
+            vec![DEFAULT_ADDRESS.to_string()]
+        }
+    }
+
+    /// The history database: `--database`, else the one in the config file.
+    pub fn database(&self, history: &HistoryConfig) -> Option<PathBuf> {
+        self.database.clone().or_else(|| history.database.clone())
+    }
+
+    pub fn timeout(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.timeout as i64)
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Info(InfoCommand),
+    Players(PlayersCommand),
+    Rules(RulesCommand),
+    Ping(PingCommand),
+    Exporter(ExporterCommand),
+    Api(ApiCommand),
+    Record(RecordCommand),
+}
+
+#[derive(FromArgs)]
+/// Print the server info.
+#[argh(subcommand, name = "info")]
+pub struct InfoCommand {
+    #[argh(positional)]
+    /// server address (e.g. 127.0.0.1:27015) or favorite nickname
+    address: String,
+
+    #[argh(option, short = 'f', default = "OutputFormat::Table")]
+    /// output format: json, csv or table (default)
+    format: OutputFormat,
+}
+
+#[derive(FromArgs)]
+/// Print the players currently on the server.
+#[argh(subcommand, name = "players")]
+pub struct PlayersCommand {
+    #[argh(positional)]
+    /// server address (e.g. 127.0.0.1:27015) or favorite nickname
+    address: String,
+
+    #[argh(option, short = 'f', default = "OutputFormat::Table")]
+    /// output format: json, csv or table (default)
+    format: OutputFormat,
+}
+
+#[derive(FromArgs)]
+/// Print the server rules (cvars).
+#[argh(subcommand, name = "rules")]
+pub struct RulesCommand {
+    #[argh(positional)]
+    /// server address (e.g. 127.0.0.1:27015) or favorite nickname
+    address: String,
+
+    #[argh(option, short = 'f', default = "OutputFormat::Table")]
+    /// output format: json, csv or table (default)
+    format: OutputFormat,
+}
+
+#[derive(FromArgs)]
+/// Print the round trip time to the server.
+#[argh(subcommand, name = "ping")]
+pub struct PingCommand {
+    #[argh(positional)]
+    /// server address (e.g. 127.0.0.1:27015) or favorite nickname
+    address: String,
+
+    #[argh(switch)]
+    /// ping every address the host name resolves to, not just the first that answers
+    all: bool,
+
+    #[argh(option, short = 'f', default = "OutputFormat::Table")]
+    /// output format: json, csv or table (default)
+    format: OutputFormat,
+}
+
+#[derive(FromArgs)]
+/// Poll the watched servers and serve Prometheus metrics on /metrics.
+#[argh(subcommand, name = "exporter")]
+pub struct ExporterCommand {
+    #[argh(option, short = 'l', default = "String::from(\"0.0.0.0:9877\")")]
+    /// address to serve the metrics on (default 0.0.0.0:9877)
+    listen: String,
+
+    #[argh(option, short = 'i', default = "15")]
+    /// seconds between polls of each server (default 15)
+    interval: u64,
+}
+
+#[derive(FromArgs)]
+/// Poll the watched servers and serve their cached info, players and rules as JSON.
+#[argh(subcommand, name = "api")]
+pub struct ApiCommand {
+    #[argh(option, short = 'l', default = "String::from(\"0.0.0.0:9878\")")]
+    /// address to serve the api on (default 0.0.0.0:9878)
+    listen: String,
+
+    #[argh(option, short = 'i', default = "15")]
+    /// seconds between polls of each server (default 15)
+    interval: u64,
+}
+
+#[derive(FromArgs)]
+/// Poll the watched servers and record every result in the history database.
+#[argh(subcommand, name = "record")]
+pub struct RecordCommand {
+    #[argh(option, short = 'i', default = "60")]
+    /// seconds between polls of each server (default 60)
+    interval: u64,
+
+    #[argh(option)]
+    /// days of history to keep, 0 keeps everything (default from the config file, else 30)
+    retention_days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Ping {
+    address: String,
+    /// The resolved address that was pinged.
+    peer: String,
+    ping_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Ping {
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.address.clone(),
+            self.peer.clone(),
+            self.ping_ms.map(|ping_ms| ping_ms.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+impl Tabular for Ping {
+    fn header(&self) -> Vec<&'static str> {
+        vec!["Address", "Answered by", "Ping (ms)"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![self.row()]
+    }
+}
+
+/// One row per resolved address, the ones that did not answer have an error.
+impl Tabular for Vec<Ping> {
+    fn header(&self) -> Vec<&'static str> {
+        vec!["Address", "Resolved", "Ping (ms)", "Error"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter()
+            .map(|ping| {
+                let mut row = ping.row();
+                row.push(ping.error.clone().unwrap_or_default());
+                row
+            })
+            .collect()
+    }
+}
+
+/// Runs the subcommand and returns the process exit code.
+pub fn run(command: Command, args: &Args) -> i32 {
+    games::install(GameRegistry::new(&load_settings().games));
+
+    match command {
+        Command::Info(command) => query(&command.address, args, command.format, |server| server.get_server_info()),
+        Command::Players(command) => query(&command.address, args, command.format, |server| server.get_players()),
+        Command::Rules(command) => query(&command.address, args, command.format, |server| server.get_rules()),
+        Command::Ping(command) if command.all => ping_all(&command, args),
+        Command::Ping(command) => {
+            let address = command.address.clone();
+            query(&command.address, args, command.format, move |server| match server.ping() {
+                Response::Ok(ping) => Response::Ok(Ping {
+                    address,
+                    peer: server.peer().to_string(),
+                    ping_ms: Some(ping.num_milliseconds()),
+                    error: None,
+                }),
+                Response::Error(err) => Response::Error(err),
+            })
+        }
+        Command::Exporter(command) => {
+            let favorites = load_favorites();
+            let store = match open_store(args, None) {
+                Ok(store) => store,
+                Err(code) => return code,
+            };
+            let statuses = poller::spawn(&args.addresses(&favorites), poll_config(args, command.interval, false), store);
+
+            exit_code(metrics::serve(&command.listen, statuses))
+        }
+        Command::Api(command) => {
+            let favorites = load_favorites();
+            let store = match open_store(args, None) {
+                Ok(store) => store,
+                Err(code) => return code,
+            };
+            let statuses = poller::spawn(&args.addresses(&favorites), poll_config(args, command.interval, true), store);
+
+            exit_code(api::serve(&command.listen, statuses))
+        }
+        Command::Record(command) => {
+            let favorites = load_favorites();
+            let store = match open_store(args, Some(&command)) {
+                Ok(store) => store.expect("The record command always opens a database"),
+                Err(code) => return code,
+            };
+            poller::spawn(&args.addresses(&favorites), poll_config(args, command.interval, false), Some(store));
+
+            // The pollers record and prune on their own threads.
+            loop {
+                thread::park();
+            }
+        }
+    }
+}
+
+/// Polls every `interval` seconds, with the player thresholds of the watchlist so every
+/// mode records the same server events as the interface.
+fn poll_config(args: &Args, interval: u64, with_rules: bool) -> PollConfig {
+    PollConfig {
+        interval: chrono::Duration::seconds(interval.max(1) as i64),
+        timeout: args.timeout(),
+        family: args.family,
+        decoding: args.decoding,
+        with_rules,
+        thresholds: load_settings().watchlist.player_thresholds,
+    }
+}
+
+fn exit_code(result: Result<(), String>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            EXIT_CONNECT_FAILED
+        }
+    }
+}
+
+fn load_favorites() -> Favorites {
+    Favorites::load().unwrap_or_else(|err| {
+        eprintln!("Ignoring the favorites, {}", err);
+        Favorites::default()
+    })
+}
+
+fn load_settings() -> Settings {
+    Settings::load().unwrap_or_else(|err| {
+        eprintln!("Ignoring the config file, {}", err);
+        Settings::default()
+    })
+}
+
+/// Opens the history database of `--database` or the config file, the record command
+/// falls back to the default database. Errors are printed and give the exit code.
+fn open_store(args: &Args, record: Option<&RecordCommand>) -> Result<Option<Arc<Mutex<Store>>>, i32> {
+    let history = load_settings().history;
+    let path = match (args.database(&history), record) {
+        (Some(path), _) => path,
+        (None, Some(_)) => Store::default_path(),
+        (None, None) => return Ok(None),
+    };
+    let retention_days = record
+        .and_then(|record| record.retention_days)
+        .unwrap_or(history.retention_days);
+
+    match Store::open_with_retention(&path, retention_days) {
+        Ok(store) => Ok(Some(Arc::new(Mutex::new(store)))),
+        Err(err) => {
+            eprintln!("{}", err);
+            Err(EXIT_CONNECT_FAILED)
+        }
+    }
+}
+
+/// Pings every resolved address of the ping command on its own, fails only when none
+/// of them answered.
+fn ping_all(command: &PingCommand, args: &Args) -> i32 {
+    let address = load_favorites().resolve(&command.address);
+    let resolved = match server::resolve(&address, args.family) {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_CONNECT_FAILED;
+        }
+    };
+
+    let pings: Vec<Ping> = resolved
+        .iter()
+        .map(|resolved| {
+            let result = match Server::connect(&resolved.to_string(), AddressFamily::Any, args.timeout()) {
+                Response::Ok(mut server) => server.ping(),
+                Response::Error(err) => Response::Error(err),
+            };
+            let (ping_ms, error) = match result {
+                Response::Ok(ping) => (Some(ping.num_milliseconds()), None),
+                Response::Error(err) => (None, Some(err.to_string())),
+            };
+
+            Ping { address: address.clone(), peer: resolved.to_string(), ping_ms, error }
+        })
+        .collect();
+
+    println!("{}", output::render(&pings, command.format));
+
+    if pings.iter().any(|ping| ping.ping_ms.is_some()) { 0 } else { EXIT_QUERY_FAILED }
+}
+
+fn query<T, F>(address: &str, args: &Args, format: OutputFormat, request: F) -> i32
+where
+    T: Serialize + Tabular,
+    F: FnOnce(&mut Server) -> Response<T>,
+{
+    let address = load_favorites().resolve(address);
+
+    let mut server = match Server::connect(&address, args.family, args.timeout()) {
+        Response::Ok(server) => server,
+        Response::Error(err) => {
+            eprintln!("{}", err);
+            return EXIT_CONNECT_FAILED;
+        }
+    };
+
+    server.set_decoding(args.decoding);
+
+    match request(&mut server) {
+        Response::Ok(value) => {
+            println!("{}", output::render(&value, format));
+            0
+        }
+        Response::Error(err) => {
+            eprintln!("{}", err);
+            EXIT_QUERY_FAILED
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::games::GameConfig;
+use crate::keys::KeysConfig;
+use crate::store::HistoryConfig;
+use crate::theme::ThemeConfig;
+use crate::watchlist::WatchlistConfig;
+
+const APP_DIR: &str = "valve-server-reader";
+
+/// `$XDG_CONFIG_HOME/valve-server-reader`, falling back to `~/.config/valve-server-reader`.
+pub fn config_dir() -> PathBuf {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config"))
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+
+    base.join(APP_DIR)
+}
+
+/// `$XDG_DATA_HOME/valve-server-reader`, falling back to `~/.local/share/valve-server-reader`.
+pub fn data_dir() -> PathBuf {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("share"))
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+
+    base.join(APP_DIR)
+}
+
+/// The hand written `config.toml`, every section is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub watchlist: WatchlistConfig,
+    pub history: HistoryConfig,
+    pub keys: KeysConfig,
+    pub theme: ThemeConfig,
+    /// Games added to the built in app ID registry, see `games::GameConfig`.
+    pub games: Vec<GameConfig>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, String> {
+        Settings::load_from(&config_dir().join("config.toml"))
+    }
+
+    /// Loads the settings from `path`, a missing file gives the defaults.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}, error: {}", path.display(), err))?;
+
+        toml::from_str(&content)
+            .map_err(|err| format!("Failed to parse {}, error: {}", path.display(), err))
+    }
+}
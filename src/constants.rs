@@ -18,15 +18,15 @@ pub const RULES_CHALLANGE_REUEST: [u8; 9] = [
     0xFF, 0xFF, 0xFF, 0xFF
 ];
 
-pub const PING_REQUEST: [u8; 5] = [
-    0xFF, 0xFF, 0xFF, 0xFF, 0x69,
-];
-
-pub const SERVER_QUERY_CHALLANGE_REQUEST: [u8; 5] = [
-    0xFF, 0xFF, 0xFF, 0xFF, 0x57,
-];
-
 pub const SIMPLE_RESPONSE_HEADER: u32 = 0xFFFFFFFF;
 pub const MULTI_PACKET_RESPONSE_HEADER: u32 = 0xFFFFFFFE;
 
-pub const SHIP_GAME_ID: i16 = 2400;
\ No newline at end of file
+/// Port of addresses given without one.
+pub const DEFAULT_PORT: u16 = 27015;
+
+// Extra data flag (EDF) bits of the server info.
+pub const EDF_PORT: u8 = 0x80;
+pub const EDF_STEAM_ID: u8 = 0x10;
+pub const EDF_SOURCE_TV: u8 = 0x40;
+pub const EDF_KEYWORDS: u8 = 0x20;
+pub const EDF_GAME_ID: u8 = 0x01;
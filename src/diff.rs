@@ -0,0 +1,159 @@
+use chrono::Duration;
+use std::fmt;
+
+use crate::output::format_duration;
+use crate::server::Player;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+    Joined { name: String, duration: Duration, score: i32 },
+    Left { name: String, duration: Duration },
+    Renamed { from: String, to: String },
+    ScoreChanged { name: String, from: i32, to: i32 },
+}
+
+impl fmt::Display for PlayerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayerEvent::Joined { name, .. } => write!(f, "{} joined", name),
+            PlayerEvent::Left { name, duration } => write!(f, "{} left after {}", name, format_duration(duration)),
+            PlayerEvent::Renamed { from, to } => write!(f, "{} is now known as {}", from, to),
+            PlayerEvent::ScoreChanged { name, from, to } => write!(f, "{} score {} -> {}", name, from, to),
+        }
+    }
+}
+
+/// Compares two consecutive player lists taken `elapsed` apart.
+///
+/// A2S gives players no stable id, so a player is the same one when the name
+/// matches and the connection time kept counting. A connection time that kept
+/// counting under another name is a rename, anything left over joined or left.
+pub fn diff_players(previous: &[Player], current: &[Player], elapsed: Duration) -> Vec<PlayerEvent> {
+    let slack = Duration::seconds(3).max(elapsed / 2);
+    let expected = |player: &Player| player.duration + elapsed;
+    let distance = |old: &Player, new: &Player| (new.duration - expected(old)).num_milliseconds().abs();
+
+    let mut events = vec![];
+    let mut unmatched_previous: Vec<&Player> = previous.iter().collect();
+    let mut unmatched_current = vec![];
+
+    for player in current {
+        let same = unmatched_previous
+            .iter()
+            .enumerate()
+            .filter(|(_, old)| old.name == player.name && player.duration + slack >= expected(old))
+            .min_by_key(|(_, old)| distance(old, player))
+            .map(|(i, _)| i);
+
+        match same {
+            Some(i) => {
+                let old = unmatched_previous.remove(i);
+                if old.score != player.score {
+                    events.push(PlayerEvent::ScoreChanged { name: player.name.clone(), from: old.score, to: player.score });
+                }
+            }
+            None => unmatched_current.push(player),
+        }
+    }
+
+    for player in unmatched_current {
+        let renamed = unmatched_previous
+            .iter()
+            .enumerate()
+            .filter(|(_, old)| distance(old, player) <= slack.num_milliseconds())
+            .min_by_key(|(_, old)| distance(old, player))
+            .map(|(i, _)| i);
+
+        match renamed {
+            Some(i) => {
+                let old = unmatched_previous.remove(i);
+                events.push(PlayerEvent::Renamed { from: old.name.clone(), to: player.name.clone() });
+                if old.score != player.score {
+                    events.push(PlayerEvent::ScoreChanged { name: player.name.clone(), from: old.score, to: player.score });
+                }
+            }
+            None => events.push(PlayerEvent::Joined { name: player.name.clone(), duration: player.duration, score: player.score }),
+        }
+    }
+
+    for old in unmatched_previous {
+        events.push(PlayerEvent::Left { name: old.name.clone(), duration: old.duration });
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, score: i32, seconds: i64) -> Player {
+        Player {
+            index: 0,
+            name: name.to_string(),
+            raw_name: name.as_bytes().to_vec(),
+            score,
+            duration: Duration::seconds(seconds),
+            deaths: 0,
+            money: None,
+        }
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let previous = vec![player("alice", 1, 100), player("bob", 2, 50)];
+        let current = vec![player("bob", 2, 60), player("alice", 1, 110)];
+
+        assert!(diff_players(&previous, &current, Duration::seconds(10)).is_empty());
+    }
+
+    #[test]
+    fn test_join_leave_and_score_change() {
+        let previous = vec![player("alice", 1, 100), player("bob", 2, 50)];
+        let current = vec![player("alice", 4, 110), player("carol", 0, 5)];
+
+        assert_eq!(
+            vec![
+                PlayerEvent::ScoreChanged { name: "alice".to_string(), from: 1, to: 4 },
+                PlayerEvent::Joined { name: "carol".to_string(), duration: Duration::seconds(5), score: 0 },
+                PlayerEvent::Left { name: "bob".to_string(), duration: Duration::seconds(50) },
+            ],
+            diff_players(&previous, &current, Duration::seconds(10)));
+    }
+
+    #[test]
+    fn test_rename_keeps_connection_time() {
+        let previous = vec![player("alice", 1, 100)];
+        let current = vec![player("alice2", 1, 111)];
+
+        assert_eq!(
+            vec![PlayerEvent::Renamed { from: "alice".to_string(), to: "alice2".to_string() }],
+            diff_players(&previous, &current, Duration::seconds(10)));
+    }
+
+    #[test]
+    fn test_reconnect_with_same_name_is_leave_and_join() {
+        let previous = vec![player("alice", 7, 600)];
+        let current = vec![player("alice", 0, 4)];
+
+        assert_eq!(
+            vec![
+                PlayerEvent::Joined { name: "alice".to_string(), duration: Duration::seconds(4), score: 0 },
+                PlayerEvent::Left { name: "alice".to_string(), duration: Duration::seconds(600) },
+            ],
+            diff_players(&previous, &current, Duration::seconds(10)));
+    }
+
+    #[test]
+    fn test_duplicate_names_match_by_duration() {
+        let previous = vec![player("unnamed", 1, 100), player("unnamed", 2, 500)];
+        let current = vec![player("unnamed", 3, 510)];
+
+        assert_eq!(
+            vec![
+                PlayerEvent::ScoreChanged { name: "unnamed".to_string(), from: 2, to: 3 },
+                PlayerEvent::Left { name: "unnamed".to_string(), duration: Duration::seconds(100) },
+            ],
+            diff_players(&previous, &current, Duration::seconds(10)));
+    }
+}
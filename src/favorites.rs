@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Favorite {
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl Favorite {
+    pub fn new(address: &str) -> Self {
+        Favorite {
+            address: address.to_string(),
+            nickname: None,
+            group: None,
+            notes: None,
+        }
+    }
+
+    /// The nickname when there is one, the address otherwise.
+    pub fn title(&self) -> &str {
+        self.nickname.as_deref().unwrap_or(&self.address)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.address == name || self.nickname.as_deref() == Some(name)
+    }
+}
+
+/// The favorite servers, stored as `[[favorite]]` tables in `favorites.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Favorites {
+    #[serde(default, rename = "favorite")]
+    pub servers: Vec<Favorite>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Favorites {
+    pub fn default_path() -> PathBuf {
+        config::config_dir().join("favorites.toml")
+    }
+
+    pub fn load() -> Result<Self, String> {
+        Favorites::load_from(&Favorites::default_path())
+    }
+
+    /// Loads the favorites from `path`, a missing file is an empty list.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let mut favorites: Favorites = if path.exists() {
+            let content = fs::read_to_string(path)
+                .map_err(|err| format!("Failed to read {}, error: {}", path.display(), err))?;
+            toml::from_str(&content)
+                .map_err(|err| format!("Failed to parse {}, error: {}", path.display(), err))?
+        } else {
+            Favorites::default()
+        };
+
+        favorites.path = path.to_path_buf();
+
+        Ok(favorites)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("Failed to create {}, error: {}", dir.display(), err))?;
+        }
+
+        let content = toml::to_string_pretty(self)
+            .map_err(|err| format!("Failed to serialize favorites, error: {}", err))?;
+
+        fs::write(&self.path, content)
+            .map_err(|err| format!("Failed to write {}, error: {}", self.path.display(), err))
+    }
+
+    /// Finds a favorite by nickname or address.
+    pub fn find(&self, name: &str) -> Option<&Favorite> {
+        self.servers.iter().find(|favorite| favorite.matches(name))
+    }
+
+    /// Returns the address for a nickname, anything else is assumed to be an address already.
+    pub fn resolve(&self, name: &str) -> String {
+        match self.find(name) {
+            Some(favorite) => favorite.address.clone(),
+            None => name.to_string(),
+        }
+    }
+
+    /// Adds the server, or updates the nickname of an already known address.
+    pub fn add(&mut self, address: &str, nickname: Option<String>) {
+        match self.servers.iter_mut().find(|favorite| favorite.address == address) {
+            Some(favorite) => {
+                if nickname.is_some() {
+                    favorite.nickname = nickname;
+                }
+            }
+            None => self.servers.push(Favorite { nickname, ..Favorite::new(address) }),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Favorite> {
+        let index = self.servers.iter().position(|favorite| favorite.matches(name))?;
+
+        Some(self.servers.remove(index))
+    }
+
+    pub fn rename(&mut self, name: &str, nickname: &str) -> bool {
+        match self.servers.iter_mut().find(|favorite| favorite.matches(name)) {
+            Some(favorite) => {
+                favorite.nickname = Some(nickname.to_string()).filter(|nickname| !nickname.is_empty());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn favorites() -> Favorites {
+        let mut favorites = Favorites::default();
+        favorites.add("127.0.0.1:27015", Some("home".to_string()));
+        favorites.add("127.0.0.1:27016", None);
+        favorites
+    }
+
+    #[test]
+    fn test_resolve_nickname_and_address() {
+        let favorites = favorites();
+
+        assert_eq!("127.0.0.1:27015", favorites.resolve("home"));
+        assert_eq!("127.0.0.1:27016", favorites.resolve("127.0.0.1:27016"));
+        assert_eq!("10.0.0.1:27015", favorites.resolve("10.0.0.1:27015"));
+    }
+
+    #[test]
+    fn test_add_existing_address_updates_nickname() {
+        let mut favorites = favorites();
+
+        favorites.add("127.0.0.1:27016", Some("work".to_string()));
+
+        assert_eq!(2, favorites.servers.len());
+        assert_eq!("work", favorites.servers[1].title());
+    }
+
+    #[test]
+    fn test_rename_and_remove() {
+        let mut favorites = favorites();
+
+        assert!(favorites.rename("home", "cabin"));
+        assert!(!favorites.rename("home", "cabin"));
+        assert_eq!("127.0.0.1:27015", favorites.remove("cabin").unwrap().address);
+        assert_eq!(1, favorites.servers.len());
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let mut favorites = favorites();
+        favorites.servers[0].group = Some("eu".to_string());
+
+        let content = toml::to_string_pretty(&favorites).unwrap();
+        let parsed: Favorites = toml::from_str(&content).unwrap();
+
+        assert!(content.contains("[[favorite]]"));
+        assert_eq!(favorites.servers, parsed.servers);
+    }
+}
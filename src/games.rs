@@ -0,0 +1,235 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    GoldSource,
+    Source,
+    Source2,
+}
+
+impl Engine {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Engine::GoldSource => "GoldSource",
+            Engine::Source     => "Source",
+            Engine::Source2    => "Source 2",
+        }
+    }
+}
+
+/// Ways the responses of a game differ from the plain protocol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quirk {
+    /// The info has the mode, witness count and duration of The Ship before the version.
+    ShipInfo,
+    /// Every player is followed by their deaths and money.
+    ShipPlayers,
+    /// Servers do not answer rules queries.
+    NoRules,
+    /// The packets of split responses have no maximum size, like in early Source games.
+    NoSplitSize,
+}
+
+/// How the header of every packet of a split response is laid out, after the split
+/// header and the response ID.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SplitHeader {
+    /// One byte, the packet number in the upper and the total in the lower four bits.
+    GoldSource,
+    /// The total and the packet number, then the maximum packet size.
+    Source,
+    /// The total and the packet number.
+    SourceWithoutSize,
+}
+
+/// Which game's conventions the keywords follow, see `tags::Tags`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagDialect {
+    Plain,
+    Tf2,
+    CounterStrike,
+    Left4Dead,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub app_id: u16,
+    pub name: String,
+    pub engine: Engine,
+    pub quirks: Vec<Quirk>,
+    pub tags: TagDialect,
+}
+
+impl Game {
+    pub fn has(&self, quirk: Quirk) -> bool {
+        self.quirks.contains(&quirk)
+    }
+
+    /// Whether players come with more than a name, score and duration.
+    pub fn extra_player_fields(&self) -> bool {
+        self.has(Quirk::ShipPlayers)
+    }
+
+    pub fn split_header(&self) -> SplitHeader {
+        match self.engine {
+            Engine::GoldSource => SplitHeader::GoldSource,
+            _ if self.has(Quirk::NoSplitSize) => SplitHeader::SourceWithoutSize,
+            _ => SplitHeader::Source,
+        }
+    }
+}
+
+/// An entry of the `[[games]]` section of the config file. It adds a game, or replaces
+/// the built in one with the same app ID:
+///
+/// ```toml
+/// [[games]]
+/// app_id = 4000
+/// name = "Garry's Mod"
+/// engine = "source"          # gold_source, source (default) or source2
+/// quirks = ["no_rules"]      # ship_info, ship_players, no_rules, no_split_size
+/// tags = "plain"             # plain (default), tf2, counter_strike or left4_dead
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct GameConfig {
+    pub app_id: u16,
+    pub name: String,
+    pub engine: Option<Engine>,
+    #[serde(default)]
+    pub quirks: Vec<Quirk>,
+    pub tags: Option<TagDialect>,
+}
+
+const BUILTIN: [(u16, &str, Engine, &[Quirk], TagDialect); 23] = [
+    (10,    "Counter-Strike",                  Engine::GoldSource, &[], TagDialect::Plain),
+    (20,    "Team Fortress Classic",           Engine::GoldSource, &[], TagDialect::Plain),
+    (30,    "Day of Defeat",                   Engine::GoldSource, &[], TagDialect::Plain),
+    (40,    "Deathmatch Classic",              Engine::GoldSource, &[], TagDialect::Plain),
+    (50,    "Half-Life: Opposing Force",       Engine::GoldSource, &[], TagDialect::Plain),
+    (60,    "Ricochet",                        Engine::GoldSource, &[], TagDialect::Plain),
+    (70,    "Half-Life",                       Engine::GoldSource, &[], TagDialect::Plain),
+    (80,    "Counter-Strike: Condition Zero",  Engine::GoldSource, &[], TagDialect::Plain),
+    (130,   "Half-Life: Blue Shift",           Engine::GoldSource, &[], TagDialect::Plain),
+    (215,   "Source SDK Base 2006",            Engine::Source,     &[Quirk::NoSplitSize], TagDialect::Plain),
+    (240,   "Counter-Strike: Source",          Engine::Source,     &[], TagDialect::CounterStrike),
+    (300,   "Day of Defeat: Source",           Engine::Source,     &[], TagDialect::Plain),
+    (320,   "Half-Life 2: Deathmatch",         Engine::Source,     &[], TagDialect::Plain),
+    (440,   "Team Fortress 2",                 Engine::Source,     &[], TagDialect::Tf2),
+    (500,   "Left 4 Dead",                     Engine::Source,     &[], TagDialect::Left4Dead),
+    (550,   "Left 4 Dead 2",                   Engine::Source,     &[], TagDialect::Left4Dead),
+    (630,   "Alien Swarm",                     Engine::Source,     &[], TagDialect::Plain),
+    (730,   "Counter-Strike 2",                Engine::Source2,    &[Quirk::NoRules], TagDialect::CounterStrike),
+    (2400,  "The Ship",                        Engine::Source,     &[Quirk::ShipInfo, Quirk::ShipPlayers], TagDialect::Plain),
+    (4000,  "Garry's Mod",                     Engine::Source,     &[], TagDialect::Plain),
+    (17520, "Synergy",                         Engine::Source,     &[], TagDialect::Plain),
+    (17550, "Eternal Silence",                 Engine::Source,     &[Quirk::NoSplitSize], TagDialect::Plain),
+    (17700, "Insurgency",                      Engine::Source,     &[Quirk::NoSplitSize], TagDialect::Plain),
+];
+
+/// What is known about the games by app ID.
+#[derive(Debug, Clone)]
+pub struct GameRegistry {
+    games: HashMap<u16, Game>,
+}
+
+impl GameRegistry {
+    pub fn builtin() -> Self {
+        let games = BUILTIN
+            .iter()
+            .map(|(app_id, name, engine, quirks, tags)| (*app_id, Game {
+                app_id: *app_id,
+                name: name.to_string(),
+                engine: *engine,
+                quirks: quirks.to_vec(),
+                tags: *tags,
+            }))
+            .collect();
+
+        GameRegistry { games }
+    }
+
+    /// The built in games with the ones of the config file added.
+    pub fn new(config: &[GameConfig]) -> Self {
+        let mut registry = GameRegistry::builtin();
+
+        for game in config {
+            registry.games.insert(game.app_id, Game {
+                app_id: game.app_id,
+                name: game.name.clone(),
+                engine: game.engine.unwrap_or(Engine::Source),
+                quirks: game.quirks.clone(),
+                tags: game.tags.unwrap_or(TagDialect::Plain),
+            });
+        }
+
+        registry
+    }
+
+    pub fn get(&self, app_id: u16) -> Option<&Game> {
+        self.games.get(&app_id)
+    }
+
+    pub fn has(&self, app_id: u16, quirk: Quirk) -> bool {
+        self.get(app_id).is_some_and(|game| game.has(quirk))
+    }
+}
+
+static REGISTRY: OnceLock<GameRegistry> = OnceLock::new();
+
+/// Makes `registry` the one returned by `registry()`, only the first call counts.
+pub fn install(registry: GameRegistry) {
+    let _ = REGISTRY.set(registry);
+}
+
+/// The installed registry, the built in one when none was installed.
+pub fn registry() -> &'static GameRegistry {
+    REGISTRY.get_or_init(GameRegistry::builtin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let registry = GameRegistry::builtin();
+
+        assert!(registry.has(2400, Quirk::ShipInfo));
+        assert!(registry.get(2400).is_some_and(Game::extra_player_fields));
+        assert_eq!(Some(TagDialect::Tf2), registry.get(440).map(|game| game.tags));
+        assert!(!registry.has(440, Quirk::NoRules));
+        assert!(registry.get(12345).is_none());
+        assert_eq!(Some(SplitHeader::GoldSource), registry.get(10).map(Game::split_header));
+        assert_eq!(Some(SplitHeader::SourceWithoutSize), registry.get(17550).map(Game::split_header));
+        assert_eq!(Some(SplitHeader::Source), registry.get(440).map(Game::split_header));
+    }
+
+    #[test]
+    fn test_config_adds_and_replaces_games() {
+        let config: Vec<GameConfig> = toml::from_str::<HashMap<String, Vec<GameConfig>>>(r#"
+            [[games]]
+            app_id = 12345
+            name = "Some Mod"
+            quirks = ["no_rules"]
+            tags = "tf2"
+
+            [[games]]
+            app_id = 440
+            name = "TF2 Classic"
+            engine = "gold_source"
+        "#).unwrap().remove("games").unwrap();
+
+        let registry = GameRegistry::new(&config);
+
+        assert!(registry.has(12345, Quirk::NoRules));
+        assert_eq!(Some(Engine::Source), registry.get(12345).map(|game| game.engine));
+        assert_eq!(Some(TagDialect::Tf2), registry.get(12345).map(|game| game.tags));
+        assert_eq!(Some("TF2 Classic"), registry.get(440).map(|game| game.name.as_str()));
+        assert_eq!(Some(TagDialect::Plain), registry.get(440).map(|game| game.tags));
+    }
+}
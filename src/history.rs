@@ -0,0 +1,187 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// One poll, the counts are `None` when it failed so the graphs can show the gap.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub time: DateTime<Utc>,
+    pub players: Option<u8>,
+    pub max_players: Option<u8>,
+    pub ping: Option<Duration>,
+}
+
+impl Sample {
+    pub fn failed(time: DateTime<Utc>) -> Self {
+        Sample { time, players: None, max_players: None, ping: None }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeWindow {
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    SixHours,
+}
+
+impl TimeWindow {
+    pub fn duration(&self) -> Duration {
+        match self {
+            TimeWindow::FiveMinutes    => Duration::minutes(5),
+            TimeWindow::FifteenMinutes => Duration::minutes(15),
+            TimeWindow::OneHour        => Duration::hours(1),
+            TimeWindow::SixHours       => Duration::hours(6),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimeWindow::FiveMinutes    => "5m",
+            TimeWindow::FifteenMinutes => "15m",
+            TimeWindow::OneHour        => "1h",
+            TimeWindow::SixHours       => "6h",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            TimeWindow::FiveMinutes    => TimeWindow::FifteenMinutes,
+            TimeWindow::FifteenMinutes => TimeWindow::OneHour,
+            TimeWindow::OneHour        => TimeWindow::SixHours,
+            TimeWindow::SixHours       => TimeWindow::FiveMinutes,
+        }
+    }
+}
+
+/// Rolling player count and ping history of a server, old samples are dropped
+/// once they fall out of the largest time window.
+#[derive(Debug, Default)]
+pub struct History {
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        let oldest = sample.time - TimeWindow::SixHours.duration();
+
+        while self.samples.front().is_some_and(|front| front.time < oldest) {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    /// Samples newer than `now - window`, oldest first.
+    pub fn window(&self, window: TimeWindow, now: DateTime<Utc>) -> impl Iterator<Item = &Sample> {
+        let oldest = now - window.duration();
+
+        self.samples.iter().filter(move |sample| sample.time >= oldest)
+    }
+
+    /// `(seconds before now, players)` points for a chart, one line per run of answered polls.
+    pub fn players_points(&self, window: TimeWindow, now: DateTime<Utc>) -> Vec<Vec<(f64, f64)>> {
+        self.lines(window, now, |sample| sample.players.map(f64::from))
+    }
+
+    /// `(seconds before now, ping in milliseconds)` points for a chart, one line per run of answered polls.
+    pub fn ping_points(&self, window: TimeWindow, now: DateTime<Utc>) -> Vec<Vec<(f64, f64)>> {
+        self.lines(window, now, |sample| sample.ping.map(|ping| ping.num_milliseconds() as f64))
+    }
+
+    /// Splits the points where `value` has none, so a chart does not draw across a failed poll.
+    fn lines(&self, window: TimeWindow, now: DateTime<Utc>, value: impl Fn(&Sample) -> Option<f64>) -> Vec<Vec<(f64, f64)>> {
+        let mut lines = vec![];
+        let mut line = vec![];
+
+        for sample in self.window(window, now) {
+            match value(sample) {
+                Some(value) => line.push((seconds_before(now, sample.time), value)),
+                None if !line.is_empty() => lines.push(std::mem::take(&mut line)),
+                None => {}
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(line);
+        }
+
+        lines
+    }
+}
+
+fn seconds_before(now: DateTime<Utc>, time: DateTime<Utc>) -> f64 {
+    -((now - time).num_milliseconds() as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: DateTime<Utc>, players: u8) -> Sample {
+        Sample {
+            time,
+            players: Some(players),
+            max_players: Some(32),
+            ping: Some(Duration::milliseconds(40)),
+        }
+    }
+
+    #[test]
+    fn test_window_only_returns_recent_samples() {
+        let now = Utc::now();
+        let mut history = History::new();
+
+        history.push(sample(now - Duration::minutes(10), 4));
+        history.push(sample(now - Duration::minutes(2), 6));
+        history.push(sample(now, 8));
+
+        let players: Vec<u8> = history.window(TimeWindow::FiveMinutes, now).filter_map(|sample| sample.players).collect();
+
+        assert_eq!(vec![6, 8], players);
+        assert_eq!(3, history.window(TimeWindow::FifteenMinutes, now).count());
+    }
+
+    #[test]
+    fn test_push_drops_samples_older_than_largest_window() {
+        let now = Utc::now();
+        let mut history = History::new();
+
+        history.push(sample(now - Duration::hours(7), 1));
+        history.push(sample(now, 2));
+
+        let players: Vec<u8> = history.window(TimeWindow::SixHours, now - Duration::hours(7)).filter_map(|sample| sample.players).collect();
+
+        assert_eq!(vec![2], players);
+    }
+
+    #[test]
+    fn test_points_are_relative_to_now() {
+        let now = Utc::now();
+        let mut history = History::new();
+
+        history.push(sample(now - Duration::seconds(30), 4));
+        history.push(Sample { ping: None, ..sample(now, 5) });
+
+        assert_eq!(vec![vec![(-30.0, 4.0), (0.0, 5.0)]], history.players_points(TimeWindow::FiveMinutes, now));
+        assert_eq!(vec![vec![(-30.0, 40.0)]], history.ping_points(TimeWindow::FiveMinutes, now));
+    }
+
+    #[test]
+    fn test_failed_polls_split_the_lines() {
+        let now = Utc::now();
+        let mut history = History::new();
+
+        history.push(sample(now - Duration::seconds(40), 4));
+        history.push(sample(now - Duration::seconds(30), 5));
+        history.push(Sample::failed(now - Duration::seconds(20)));
+        history.push(Sample::failed(now - Duration::seconds(10)));
+        history.push(sample(now, 6));
+
+        assert_eq!(vec![vec![(-40.0, 4.0), (-30.0, 5.0)], vec![(0.0, 6.0)]], history.players_points(TimeWindow::FiveMinutes, now));
+        assert_eq!(2, history.ping_points(TimeWindow::FiveMinutes, now).len());
+    }
+}
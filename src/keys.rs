@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use termion::event::Key;
+
+/// Everything a key can be bound to in the interactive view.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    NextTab,
+    PreviousTab,
+    AddServer,
+    Rename,
+    Remove,
+    Search,
+    NextSort,
+    ReverseSort,
+    ToggleChart,
+    NextTimeWindow,
+    ToggleLog,
+    ToggleInfo,
+    ToggleRules,
+    ToggleErrors,
+}
+
+impl Action {
+    /// The name used in the `[keys]` section of the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit           => "quit",
+            Action::Down           => "down",
+            Action::Up             => "up",
+            Action::PageDown       => "page_down",
+            Action::PageUp         => "page_up",
+            Action::First          => "first",
+            Action::Last           => "last",
+            Action::NextTab        => "next_tab",
+            Action::PreviousTab    => "previous_tab",
+            Action::AddServer      => "add_server",
+            Action::Rename         => "rename",
+            Action::Remove         => "remove",
+            Action::Search         => "search",
+            Action::NextSort       => "next_sort",
+            Action::ReverseSort    => "reverse_sort",
+            Action::ToggleChart    => "toggle_chart",
+            Action::NextTimeWindow => "next_time_window",
+            Action::ToggleLog      => "toggle_log",
+            Action::ToggleInfo     => "toggle_info",
+            Action::ToggleRules    => "toggle_rules",
+            Action::ToggleErrors   => "toggle_errors",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, _)| *action)
+            .find(|action| action.name() == value)
+            .ok_or_else(|| format!("Unknown action '{}' in [keys]", value))
+    }
+}
+
+const DEFAULT_BINDINGS: [(Action, &[&str]); 21] = [
+    (Action::Quit,           &["q"]),
+    (Action::Down,           &["Down", "j"]),
+    (Action::Up,             &["Up", "k"]),
+    (Action::PageDown,       &["PageDown", "ctrl-d"]),
+    (Action::PageUp,         &["PageUp", "ctrl-u"]),
+    (Action::First,          &["Home"]),
+    (Action::Last,           &["End"]),
+    (Action::NextTab,        &["Tab"]),
+    (Action::PreviousTab,    &["BackTab"]),
+    (Action::AddServer,      &["a"]),
+    (Action::Rename,         &["r"]),
+    (Action::Remove,         &["d"]),
+    (Action::Search,         &["/"]),
+    (Action::NextSort,       &["s"]),
+    (Action::ReverseSort,    &["S"]),
+    (Action::ToggleChart,    &["g"]),
+    (Action::NextTimeWindow, &["w"]),
+    (Action::ToggleLog,      &["l"]),
+    (Action::ToggleInfo,     &["i"]),
+    (Action::ToggleRules,    &["c"]),
+    (Action::ToggleErrors,   &["e"]),
+];
+
+/// The `[keys]` section of the config file, action names to key names, e.g.
+/// `down = ["j", "Down"]`. Actions that are not listed keep their default keys.
+pub type KeysConfig = HashMap<String, Vec<String>>;
+
+/// Parses a key name: a single character (`j`, `/`, `S`), `space`, `ctrl-x`, `alt-x`,
+/// `F1` to `F12` or one of `Up`, `Down`, `Left`, `Right`, `PageUp`, `PageDown`, `Home`,
+/// `End`, `Tab`, `BackTab`, `Enter`, `Esc`, `Backspace`, `Delete` and `Insert`.
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(Key::Char(c));
+    }
+
+    let lower = name.to_lowercase();
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some(c) = lower.strip_prefix("ctrl-").and_then(single) {
+        return Ok(Key::Ctrl(c));
+    }
+    if let Some(c) = name.get(4..).filter(|_| lower.starts_with("alt-")).and_then(single) {
+        return Ok(Key::Alt(c));
+    }
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()).filter(|n| (1..=12).contains(n)) {
+        return Ok(Key::F(n));
+    }
+
+    match lower.as_str() {
+        "space"     => Ok(Key::Char(' ')),
+        "up"        => Ok(Key::Up),
+        "down"      => Ok(Key::Down),
+        "left"      => Ok(Key::Left),
+        "right"     => Ok(Key::Right),
+        "pageup"    => Ok(Key::PageUp),
+        "pagedown"  => Ok(Key::PageDown),
+        "home"      => Ok(Key::Home),
+        "end"       => Ok(Key::End),
+        "tab"       => Ok(Key::Char('\t')),
+        "backtab"   => Ok(Key::BackTab),
+        "enter"     => Ok(Key::Char('\n')),
+        "esc"       => Ok(Key::Esc),
+        "backspace" => Ok(Key::Backspace),
+        "delete"    => Ok(Key::Delete),
+        "insert"    => Ok(Key::Insert),
+        _           => Err(format!("Unknown key '{}'", name)),
+    }
+}
+
+/// How a key is shown in the help line.
+pub fn key_label(key: &Key) -> String {
+    match key {
+        Key::Char(' ')  => "space".to_string(),
+        Key::Char('\t') => "tab".to_string(),
+        Key::Char('\n') => "enter".to_string(),
+        Key::Char(c)    => c.to_string(),
+        Key::Ctrl(c)    => format!("ctrl-{}", c),
+        Key::Alt(c)     => format!("alt-{}", c),
+        Key::F(n)       => format!("F{}", n),
+        Key::Up         => "up".to_string(),
+        Key::Down       => "down".to_string(),
+        Key::Left       => "left".to_string(),
+        Key::Right      => "right".to_string(),
+        Key::PageUp     => "pgup".to_string(),
+        Key::PageDown   => "pgdn".to_string(),
+        Key::Home       => "home".to_string(),
+        Key::End        => "end".to_string(),
+        Key::BackTab    => "shift-tab".to_string(),
+        Key::Esc        => "esc".to_string(),
+        Key::Backspace  => "backspace".to_string(),
+        Key::Delete     => "delete".to_string(),
+        Key::Insert     => "insert".to_string(),
+        _               => "?".to_string(),
+    }
+}
+
+/// Maps the keys of the interactive view to actions.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    actions: HashMap<Key, Action>,
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::new(&KeysConfig::new()).expect("the default key bindings are valid")
+    }
+}
+
+impl KeyBindings {
+    /// The default bindings with the actions of `config` rebound. A key bound in `config`
+    /// is taken away from the action it has by default.
+    pub fn new(config: &KeysConfig) -> Result<Self, String> {
+        let mut keys: HashMap<Action, Vec<Key>> = HashMap::new();
+
+        for (action, names) in DEFAULT_BINDINGS.iter() {
+            let parsed = names.iter().map(|name| parse_key(name)).collect::<Result<_, _>>()?;
+            keys.insert(*action, parsed);
+        }
+
+        let mut configured = vec![];
+        for (name, names) in config.iter() {
+            let action = name.parse::<Action>()?;
+            let parsed: Vec<Key> = names
+                .iter()
+                .map(|name| parse_key(name).map_err(|err| format!("{} for '{}' in [keys]", err, action.name())))
+                .collect::<Result<_, _>>()?;
+
+            keys.insert(action, parsed);
+            configured.push(action);
+        }
+
+        let mut actions = HashMap::new();
+        for (action, bound) in keys.iter().filter(|(action, _)| !configured.contains(action)) {
+            for key in bound {
+                actions.insert(*key, *action);
+            }
+        }
+        for action in configured.iter() {
+            for key in keys[action].iter() {
+                actions.insert(*key, *action);
+            }
+        }
+
+        // Drop the default keys that were rebound, so the help line stays truthful.
+        for (action, bound) in keys.iter_mut() {
+            bound.retain(|key| actions.get(key) == Some(action));
+        }
+
+        Ok(KeyBindings { actions, keys })
+    }
+
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        self.actions.get(key).copied()
+    }
+
+    /// The first key of `action`, `None` when it is unbound.
+    pub fn key(&self, action: Action) -> Option<Key> {
+        self.keys.get(&action).and_then(|keys| keys.first()).copied()
+    }
+
+    /// The first key of `action` as shown in the help line.
+    pub fn label(&self, action: Action) -> String {
+        match self.key(action) {
+            Some(key) => key_label(&key),
+            None => "unbound".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &[&str])]) -> KeysConfig {
+        entries
+            .iter()
+            .map(|(action, keys)| (action.to_string(), keys.iter().map(|key| key.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Ok(Key::Char('j')), parse_key("j"));
+        assert_eq!(Ok(Key::Char('S')), parse_key("S"));
+        assert_eq!(Ok(Key::PageDown), parse_key("PageDown"));
+        assert_eq!(Ok(Key::Ctrl('d')), parse_key("ctrl-d"));
+        assert_eq!(Ok(Key::Alt('X')), parse_key("alt-X"));
+        assert_eq!(Ok(Key::F(5)), parse_key("F5"));
+        assert_eq!(Ok(Key::Char('\t')), parse_key("tab"));
+        assert!(parse_key("hyper-x").is_err());
+    }
+
+    #[test]
+    fn test_defaults_include_vim_keys() {
+        let bindings = KeyBindings::default();
+
+        assert_eq!(Some(Action::Down), bindings.action(&Key::Char('j')));
+        assert_eq!(Some(Action::Up), bindings.action(&Key::Up));
+        assert_eq!(Some(Action::Last), bindings.action(&Key::End));
+        assert_eq!("q", bindings.label(Action::Quit));
+    }
+
+    #[test]
+    fn test_config_rebinds_and_takes_keys_away() {
+        let bindings = KeyBindings::new(&config(&[("quit", &["Esc"]), ("toggle_chart", &["j"])])).unwrap();
+
+        assert_eq!(Some(Action::Quit), bindings.action(&Key::Esc));
+        assert_eq!(None, bindings.action(&Key::Char('q')));
+        assert_eq!(Some(Action::ToggleChart), bindings.action(&Key::Char('j')));
+        assert_eq!(Some(Action::Down), bindings.action(&Key::Down));
+        assert_eq!("down", bindings.label(Action::Down));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert!(KeyBindings::new(&config(&[("jump", &["j"])])).is_err());
+        assert!(KeyBindings::new(&config(&[("down", &["nope"])])).is_err());
+    }
+}
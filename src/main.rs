@@ -1,115 +1,64 @@
+mod api;
+mod app;
 mod bytereader;
+mod cli;
 mod server;
 mod ui;
+mod config;
 mod constants;
+mod diff;
+mod favorites;
+mod games;
+mod history;
+mod keys;
+mod metrics;
+mod output;
+mod poller;
+mod rules;
+mod search;
+mod tags;
+mod steamid;
+mod store;
+mod theme;
 mod util;
+mod watcher;
+mod watchlist;
 
 use crate::util::{
-    event::{Event, Events},
+    event::{self, Config, Event, Events, QUERY_WORKER},
 };
 
-use crate::server::{Server, Response, ServerInfo, PlayersResponse };
-use std::{error::Error, io};
-use termion::{event::Key, input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
+use crate::app::{App, Movement, PromptKind};
+use crate::config::Settings;
+use crate::favorites::Favorites;
+use crate::games::GameRegistry;
+use crate::keys::{Action, KeyBindings};
+use crate::store::Store;
+use crate::theme::Theme;
+use crate::watchlist::Watchlist;
+use std::{error::Error, io, time::Duration};
+use termion::{
+    event::{Key, MouseButton, MouseEvent},
+    input::MouseTerminal,
+    raw::IntoRawMode,
+    screen::AlternateScreen,
+};
 use tui::{
     backend::TermionBackend,
-    layout::{Constraint, Layout},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Row, Table, TableState},
+    layout::Rect,
     Terminal,
 };
 
-pub struct StatefulTable{
-    server: Server,
-    server_info: Option<ServerInfo>,
-    players_info: Option<PlayersResponse>,
-    state: TableState,
-    items: Vec<Vec<String>>,
-}
-
-impl StatefulTable {    
-    fn new() -> StatefulTable {      
-
-        StatefulTable {
-            players_info: None,
-            server_info: None,
-            server: Server::connect("178.236.67.8:27015"),
-            state: TableState::default(),
-            items: vec![],            
-        }
-    }
-
-    pub fn get_stats(&mut self) -> Result<(ServerInfo, PlayersResponse), String> {        
-        match self.server.get_server_info() {
-            Response::Error(err) => {                
-                 Err(err)
-            },
-            Response::Ok(server_info) => {
-                match self.server.get_players() {
-                    Response::Error(err) => Err(err),
-                    Response::Ok(player_response) => {
-                        Ok((server_info, player_response))
-                    }
-                }
-            }
-        }
-    }
-
-    pub fn update(&mut self) {
-        let (server_info, players_info) = (self.get_stats()).unwrap();
-        
-        let mut players_rows = vec![];
-
-        for player in players_info.players.iter() {
-            players_rows.push(
-                vec![
-                    player.name.clone(),
-                    player.score.to_string(), 
-                    format!("{}h, {:02}m, {:02}s", 
-                        player.duration.num_hours(), 
-                        player.duration.num_minutes()-(player.duration.num_hours()*60), 
-                        player.duration.num_seconds()-(player.duration.num_minutes()*60))
-                ]
-        );
-        }
-        
-        self.server_info = Some(server_info);
-        self.items = players_rows;        
-    }
-
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }
-
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }    
-}
-
 fn setup_panic() {
     let raw_handle = std::io::stdout().into_raw_mode().unwrap();
     let default_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
+        // Query workers report panics as the error of their server, the interface keeps running.
+        if std::thread::current().name() == Some(QUERY_WORKER) {
+            event::record_panic(info);
+            return;
+        }
+
         raw_handle
             .suspend_raw_mode()
             .unwrap();
@@ -118,96 +67,125 @@ fn setup_panic() {
     }));
 }
 
+fn handle_action(app: &mut App, action: Action) {
+    match action {
+        Action::Quit => {}
+        Action::Down => app.move_row(Movement::Next),
+        Action::Up => app.move_row(Movement::Previous),
+        Action::PageDown => app.move_row(Movement::PageDown),
+        Action::PageUp => app.move_row(Movement::PageUp),
+        Action::First => app.move_row(Movement::First),
+        Action::Last => app.move_row(Movement::Last),
+        Action::NextTab => app.next_tab(),
+        Action::PreviousTab => app.previous_tab(),
+        Action::AddServer => app.start_prompt(PromptKind::AddServer),
+        Action::Rename => app.start_prompt(PromptKind::Rename),
+        Action::Remove => app.remove_current(),
+        Action::Search => app.start_prompt(PromptKind::Search { regex: false }),
+        Action::NextSort => app.table().next_sort_column(),
+        Action::ReverseSort => app.table().reverse_sort(),
+        Action::ToggleChart => app.toggle_chart(),
+        Action::NextTimeWindow => app.next_time_window(),
+        Action::ToggleLog => app.toggle_log(),
+        Action::ToggleInfo => app.toggle_info(),
+        Action::ToggleRules => app.toggle_rules(),
+        Action::ToggleErrors => app.toggle_errors(),
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: cli::Args = argh::from_env();
+
+    match args.command.take() {
+        Some(command) => std::process::exit(cli::run(command, &args)),
+        None => run_tui(&args),
+    }
+}
+
+fn run_tui(args: &cli::Args) -> Result<(), Box<dyn Error>> {
+    let settings = Settings::load()?;
+    let favorites = Favorites::load()?;
+    let addresses = args.addresses(&favorites);
+    let store = match args.database(&settings.history) {
+        Some(path) => Some(Store::open_with_retention(&path, settings.history.retention_days)?),
+        None => None,
+    };
+    games::install(GameRegistry::new(&settings.games));
+    let keys = KeyBindings::new(&settings.keys)?;
+    let theme = Theme::from_config(&settings.theme)?;
 
     setup_panic();
     // Terminal initialization
     let stdout = io::stdout().into_raw_mode()?;    
-    let stdout = AlternateScreen::from(stdout.into_raw_mode().unwrap());
+    let stdout = MouseTerminal::from(stdout);
+    let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend).unwrap();
 
     terminal.clear().unwrap();
 
-    let events = Events::new();
+    let mut events = Events::with_config(Config {
+        tick_rate: Duration::from_secs(args.interval.max(1)),
+        exit_key: keys.key(Action::Quit).unwrap_or(Key::Char('q')),
+    });
+    // The main loop handles quitting, and the exit key has to reach text prompts.
+    events.disable_exit_key();
 
-    let mut table = StatefulTable::new();
+    let mut app = App::new(&addresses, favorites, args.timeout(), args.family, args.decoding, args.sort, events.sender())?;
+    app.event_log = args.event_log.clone();
+    app.watchlist = Watchlist::new(settings.watchlist, &args.watch);
+    app.store = store;
+    app.keys = keys;
+    app.theme = theme;
 
     // Input
     loop {
-        terminal.draw(|f| {
-            let rects = Layout::default()
-                .constraints([Constraint::Percentage(100)].as_ref())
-                .margin(5)
-                .split(f.size());
-
-            let selected_style = Style::default()
-                .fg(Color::Blue);
-                
-            let normal_style = Style::default()
-                .fg(Color::White);
-
-            let header = ["Name", "Score", "Duration"];
-            
-            let rows = table
-                .items
-                .iter()
-                .map(|i| Row::StyledData(i.iter(), normal_style));
-            
-            let server_name = match &table.server_info {
-                Some(server_info) => format!("{} | Map: {} | Players: {}/{}", &server_info.name, &server_info.map, &server_info.players, &server_info.max_players),
-                _ => "Nothing to see here".to_string()
-            };
-                
-            let t = Table::new(header.iter(), rows)
-                .block(Block::default().borders(Borders::ALL).title(server_name))
-                .highlight_style(selected_style)
-                .highlight_symbol(">> ")
-                .widths(&[
-                    Constraint::Percentage(50),
-                    Constraint::Length(30),
-                    Constraint::Max(15),
-                ]);
-
-            // let t = Table::new(["label", "value"], rows: R)
-            f.render_stateful_widget(t, rects[0], &mut table.state);
-        }).expect("something went wrong");
+        terminal.draw(|f| ui::draw(f, &mut app)).expect("something went wrong");
 
         match events.next()? {
-            Event::Input(input) => match input {
-                Key::Char('q') => {
+            Event::Input(input) if app.prompt.is_some() => {
+                app.prompt_input(input);
+            }
+            Event::Input(input) => match app.keys.action(&input) {
+                Some(Action::Quit) => {
                     break;
                 }
-                Key::Down => {
-                    table.next();
+                Some(action) => {
+                    handle_action(&mut app, action);
+                }
+                None => {
+                    if let Key::Char(c) = input {
+                        if c.is_ascii_digit() && c != '0' {
+                            app.select_tab(c as usize - '1' as usize);
+                        }
+                    }
+                }
+            },
+            Event::Mouse(mouse) if app.prompt.is_none() => match mouse {
+                // Termion counts from 1, the layout from 0.
+                MouseEvent::Press(MouseButton::Left, x, y) => {
+                    app.click(x.saturating_sub(1), y.saturating_sub(1));
+                }
+                MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+                    app.move_row(Movement::ScrollDown);
                 }
-                Key::Up => {
-                    table.previous();
+                MouseEvent::Press(MouseButton::WheelUp, _, _) => {
+                    app.move_row(Movement::ScrollUp);
                 }
                 _ => {}
             },
+            Event::Mouse(_) => {}
+            Event::Resize(width, height) => {
+                terminal.resize(Rect::new(0, 0, width, height))?;
+            }
             Event::Tick => {
-                table.update(); 
+                app.tick();
+            }
+            Event::Reply(worker_id, reply) => {
+                app.handle_reply(worker_id, reply);
             }
         }        
     }
 
     Ok(())
 }
-
-// fn main() {
-//     let mut server = Server::connect("178.236.67.44:27015");
-//     match server.get_server_info() {
-//         Response::Error(err) => panic!("Failed to fetch server info. {}", err),
-//         Response::Ok(server_info) => {
-//             println!("{:#?}", server_info);
-
-//             match server.get_players() {
-//                 Response::Error(err) => panic!("Failed to fetch players. {}", err),
-//                 Response::Ok(player_response) => {
-//                     println!("{:#?}", player_response);
-//                 }
-//             }
-//         }
-//     }
-// }
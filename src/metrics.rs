@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use crate::poller::{ServerStatus, Statuses};
+
+/// Renders the statuses in the Prometheus text exposition format.
+pub fn render(statuses: &[ServerStatus]) -> String {
+    let mut out = String::new();
+
+    gauge(&mut out, "valve_server_up", "Whether the last query of the server succeeded.", statuses, |status| {
+        Some(if status.up { 1.0 } else { 0.0 })
+    });
+    gauge(&mut out, "valve_server_players", "Number of players on the server.", statuses, |status| {
+        status.server_info.as_ref().map(|server_info| server_info.players as f64)
+    });
+    gauge(&mut out, "valve_server_max_players", "Maximum number of players the server allows.", statuses, |status| {
+        status.server_info.as_ref().map(|server_info| server_info.max_players as f64)
+    });
+    gauge(&mut out, "valve_server_bots", "Number of bots on the server.", statuses, |status| {
+        status.server_info.as_ref().map(|server_info| server_info.bots as f64)
+    });
+    gauge(&mut out, "valve_server_ping_seconds", "Round trip time of the last server info query.", statuses, |status| {
+        status.ping.and_then(|ping| ping.num_microseconds()).map(|ping| ping as f64 / 1_000_000.0)
+    });
+
+    let name = "valve_server_query_failures_total";
+    writeln!(out, "# HELP {} Failed queries by kind of error.", name).unwrap();
+    writeln!(out, "# TYPE {} counter", name).unwrap();
+    for status in statuses {
+        for (kind, count) in status.failures.iter() {
+            writeln!(out, "{}{{{},kind=\"{}\"}} {}", name, labels(status), escape(kind), count).unwrap();
+        }
+    }
+
+    out
+}
+
+fn gauge<F>(out: &mut String, name: &str, help: &str, statuses: &[ServerStatus], value: F)
+where
+    F: Fn(&ServerStatus) -> Option<f64>,
+{
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} gauge", name).unwrap();
+
+    for status in statuses {
+        if let Some(value) = value(status) {
+            writeln!(out, "{}{{{}}} {}", name, labels(status), value).unwrap();
+        }
+    }
+}
+
+/// The server labels, name, map and app id are the last known ones while a server is down.
+fn labels(status: &ServerStatus) -> String {
+    let (name, map, app_id) = match &status.server_info {
+        Some(server_info) => (server_info.name.as_str(), server_info.map.as_str(), server_info.id.to_string()),
+        None => ("", "", String::new()),
+    };
+
+    format!("address=\"{}\",name=\"{}\",map=\"{}\",app_id=\"{}\"",
+        escape(&status.address), escape(name), escape(map), app_id)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves `/metrics` on `listen` until the process is stopped.
+pub fn serve(listen: &str, statuses: Statuses) -> Result<(), String> {
+    let server = tiny_http::Server::http(listen)
+        .map_err(|err| format!("Failed to listen on {}, error: {}", listen, err))?;
+
+    for request in server.incoming_requests() {
+        let response = if request.url() == "/metrics" {
+            let body = render(&statuses.read().expect("Server statuses lock is poisoned"));
+            let content_type = "Content-Type: text/plain; version=0.0.4"
+                .parse::<tiny_http::Header>()
+                .expect("Invalid content type header");
+
+            tiny_http::Response::from_string(body).with_header(content_type)
+        } else {
+            tiny_http::Response::from_string("Not found, try /metrics").with_status_code(404)
+        };
+
+        if let Err(err) = request.respond(response) {
+            eprintln!("Failed to respond to a metrics request, error: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn status(address: &str) -> ServerStatus {
+        ServerStatus {
+            address: address.to_string(),
+            up: false,
+            server_info: None,
+            players_info: None,
+            rules_info: None,
+            rules_error: None,
+            ping: None,
+            updated_at: None,
+            last_error: Some("timed out".to_string()),
+            failures: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_down_server() {
+        let mut down = status("127.0.0.1:27015");
+        down.failures.insert("timeout".to_string(), 3);
+
+        let metrics = render(&[down]);
+
+        assert!(metrics.contains("# TYPE valve_server_up gauge\nvalve_server_up{address=\"127.0.0.1:27015\",name=\"\",map=\"\",app_id=\"\"} 0\n"));
+        assert!(metrics.contains("valve_server_query_failures_total{address=\"127.0.0.1:27015\",name=\"\",map=\"\",app_id=\"\",kind=\"timeout\"} 3\n"));
+        assert!(!metrics.contains("valve_server_players{"));
+    }
+
+    #[test]
+    fn test_escape_label_values() {
+        assert_eq!("a \\\"quoted\\\" \\\\ name\\n", escape("a \"quoted\" \\ name\n"));
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::server::{PlayersResponse, RulesResponse, ServerInfo};
+use crate::tags::Tags;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "json"  => Ok(Self::Json),
+            "csv"   => Ok(Self::Csv),
+            "table" => Ok(Self::Table),
+            _       => Err(format!("Unknown output format '{}', expected json, csv or table", value)),
+        }
+    }
+}
+
+/// Anything that can be printed as rows of columns, used by the csv and table formats.
+pub trait Tabular {
+    fn header(&self) -> Vec<&'static str>;
+    fn rows(&self) -> Vec<Vec<String>>;
+}
+
+pub fn render<T: Serialize + Tabular>(value: &T, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json  => serde_json::to_string_pretty(value).expect("Failed to serialize to json"),
+        OutputFormat::Csv   => render_csv(&value.header(), &value.rows()),
+        OutputFormat::Table => render_table(&value.header(), &value.rows()),
+    }
+}
+
+pub fn format_duration(duration: &chrono::Duration) -> String {
+    format!("{}h, {:02}m, {:02}s",
+        duration.num_hours(),
+        duration.num_minutes()-(duration.num_hours()*60),
+        duration.num_seconds()-(duration.num_minutes()*60))
+}
+
+fn render_csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut lines = vec![header.iter().map(|column| escape_csv(column)).collect::<Vec<_>>().join(",")];
+
+    for row in rows {
+        lines.push(row.iter().map(|column| escape_csv(column)).collect::<Vec<_>>().join(","));
+    }
+
+    lines.join("\n")
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|column| column.chars().count()).collect();
+
+    for row in rows {
+        for (i, column) in row.iter().enumerate() {
+            widths[i] = widths[i].max(column.chars().count());
+        }
+    }
+
+    let format_row = |row: &[String]| {
+        row.iter()
+            .zip(widths.iter())
+            .map(|(column, width)| format!("{:width$}", column, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let header: Vec<String> = header.iter().map(|column| column.to_string()).collect();
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut lines = vec![format_row(&header), format_row(&separator)];
+
+    for row in rows {
+        lines.push(format_row(row));
+    }
+
+    lines.join("\n")
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl Tabular for ServerInfo {
+    fn header(&self) -> Vec<&'static str> {
+        vec!["Field", "Value"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let tags = Tags::from_server_info(self);
+
+        vec![
+            vec!["Name".to_string(), self.name.clone()],
+            vec!["Map".to_string(), self.map.clone()],
+            vec!["Folder".to_string(), self.folder.clone()],
+            vec!["Game".to_string(), self.game.clone()],
+            vec!["App ID".to_string(), self.id.to_string()],
+            vec!["Known as".to_string(), self.game().map(|game| game.name.clone()).unwrap_or_default()],
+            vec!["Engine".to_string(), self.game().map(|game| game.engine.label().to_string()).unwrap_or_default()],
+            vec!["Players".to_string(), self.players.to_string()],
+            vec!["Max players".to_string(), self.max_players.to_string()],
+            vec!["Bots".to_string(), self.bots.to_string()],
+            vec!["Server type".to_string(), self.server_type.label().to_string()],
+            vec!["Environment".to_string(), self.environment.label().to_string()],
+            vec!["Visibility".to_string(), self.server_visibility.label().to_string()],
+            vec!["VAC".to_string(), self.vac.to_string()],
+            vec!["Version".to_string(), self.version.clone()],
+            vec!["Protocol".to_string(), self.protocol.to_string()],
+            vec!["Answered by".to_string(), optional(&self.answered_by)],
+            vec!["Port".to_string(), optional(&self.port)],
+            vec!["Steam ID".to_string(), optional(&self.steam_id)],
+            vec!["SteamID3".to_string(), self.steam_id.map(|steam_id| steam_id.steam3()).unwrap_or_default()],
+            vec!["Steam account".to_string(), self.steam_id.and_then(|steam_id| steam_id.game_server_kind()).map(|kind| kind.label().to_string()).unwrap_or_default()],
+            vec!["SourceTV port".to_string(), optional(&self.source_tv_port)],
+            vec!["SourceTV name".to_string(), optional(&self.source_tv_name)],
+            vec!["Keywords".to_string(), optional(&self.keywords)],
+            vec!["Game mode".to_string(), tags.game_mode.unwrap_or_default()],
+            vec!["Region".to_string(), tags.region.unwrap_or_default()],
+            vec!["Official".to_string(), if tags.official { "yes".to_string() } else { String::new() }],
+            vec!["Ranked".to_string(), optional(&tags.ranked)],
+            vec!["Game ID".to_string(), optional(&self.game_id)],
+            vec!["EDF".to_string(), self.edf.map(|edf| format!("{:#04x}", edf)).unwrap_or_default()],
+            vec!["Ship mode".to_string(), self.ship_mode.map(|mode| mode.label().to_string()).unwrap_or_default()],
+            vec!["Witnesses".to_string(), optional(&self.witnesses)],
+            vec!["Ship duration".to_string(), self.duration.map(|duration| format!("{}s", duration.num_seconds())).unwrap_or_default()],
+        ]
+    }
+}
+
+/// Deaths and money are only sent by The Ship.
+impl Tabular for PlayersResponse {
+    fn header(&self) -> Vec<&'static str> {
+        if self.is_ship {
+            vec!["Name", "Score", "Duration", "Deaths", "Money"]
+        } else {
+            vec!["Name", "Score", "Duration"]
+        }
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.players
+            .iter()
+            .map(|player| {
+                let mut row = vec![player.name.clone(), player.score.to_string(), format_duration(&player.duration)];
+                if self.is_ship {
+                    row.push(player.deaths.to_string());
+                    row.push(optional(&player.money));
+                }
+                row
+            })
+            .collect()
+    }
+}
+
+impl Tabular for RulesResponse {
+    fn header(&self) -> Vec<&'static str> {
+        vec!["Name", "Value"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.rules
+            .iter()
+            .map(|rule| vec![rule.name.clone(), rule.value.clone()])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{fixtures, ServerType, ServerVisibility};
+
+    #[derive(Serialize)]
+    struct Sample {
+        rows: Vec<Vec<String>>,
+    }
+
+    impl Tabular for Sample {
+        fn header(&self) -> Vec<&'static str> {
+            vec!["Name", "Value"]
+        }
+
+        fn rows(&self) -> Vec<Vec<String>> {
+            self.rows.clone()
+        }
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            rows: vec![
+                vec!["sv_tags".to_string(), "alltalk,\"nocrits\"".to_string()],
+                vec!["mp_timelimit".to_string(), "30".to_string()],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!(Ok(OutputFormat::Json), "json".parse());
+        assert_eq!(Ok(OutputFormat::Csv), "CSV".parse());
+        assert_eq!(Ok(OutputFormat::Table), "table".parse());
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_render_csv_escapes_values() {
+        assert_eq!(
+            "Name,Value\nsv_tags,\"alltalk,\"\"nocrits\"\"\"\nmp_timelimit,30",
+            render(&sample(), OutputFormat::Csv));
+    }
+
+    #[test]
+    fn test_render_table_pads_columns() {
+        assert_eq!(
+            "Name          Value\n------------  -----------------\nsv_tags       alltalk,\"nocrits\"\nmp_timelimit  30",
+            render(&sample(), OutputFormat::Table));
+    }
+
+    #[test]
+    fn test_server_info_rows_are_readable() {
+        let server_info = ServerInfo {
+            server_type: ServerType::from_byte(0x70),
+            server_visibility: ServerVisibility::Private,
+            ..fixtures::server_info("de_dust2", 0)
+        };
+        let rows = server_info.rows();
+        let value = |field: &str| rows.iter().find(|row| row[0] == field).map(|row| row[1].clone());
+
+        assert_eq!(Some("SourceTV relay".to_string()), value("Server type"));
+        assert_eq!(Some("Password protected".to_string()), value("Visibility"));
+    }
+
+    #[test]
+    fn test_ship_players_have_deaths_and_money() {
+        let mut players_info = fixtures::players(&[("alice", 5, 60)]);
+        assert_eq!("Name,Score,Duration\nalice,5,\"0h, 01m, 00s\"", render(&players_info, OutputFormat::Csv));
+
+        players_info.is_ship = true;
+        players_info.players[0].deaths = 2;
+        players_info.players[0].money = Some(1500);
+        assert_eq!("Name,Score,Duration,Deaths,Money\nalice,5,\"0h, 01m, 00s\",2,1500", render(&players_info, OutputFormat::Csv));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("1h, 02m, 03s", format_duration(&chrono::Duration::seconds(3723)));
+    }
+}
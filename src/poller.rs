@@ -0,0 +1,185 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use crate::bytereader::StringDecoding;
+use crate::server::{AddressFamily, PlayersResponse, QueryError, Response, RulesResponse, Server, ServerInfo};
+use crate::store::Store;
+use crate::watcher::ServerWatcher;
+
+/// The latest known state of a polled server.
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub address: String,
+    pub up: bool,
+    pub server_info: Option<ServerInfo>,
+    pub players_info: Option<PlayersResponse>,
+    /// Only polled when asked for, many servers do not answer rule queries. A failed rules
+    /// query keeps the last rules.
+    pub rules_info: Option<RulesResponse>,
+    /// Error of the last rules query.
+    pub rules_error: Option<String>,
+    pub ping: Option<Duration>,
+    /// Time of the last successful poll.
+    pub updated_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Failed polls by `QueryError::kind`.
+    pub failures: BTreeMap<String, u64>,
+}
+
+impl ServerStatus {
+    fn new(address: &str) -> Self {
+        ServerStatus {
+            address: address.to_string(),
+            up: false,
+            server_info: None,
+            players_info: None,
+            rules_info: None,
+            rules_error: None,
+            ping: None,
+            updated_at: None,
+            last_error: None,
+            failures: BTreeMap::new(),
+        }
+    }
+
+    /// Seconds since the last successful poll.
+    pub fn age(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.updated_at.map(|updated_at| (now - updated_at).num_seconds())
+    }
+}
+
+pub type Statuses = Arc<RwLock<Vec<ServerStatus>>>;
+
+/// How the servers are polled.
+#[derive(Debug, Clone)]
+pub struct PollConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub family: AddressFamily,
+    pub decoding: StringDecoding,
+    pub with_rules: bool,
+    /// Player counts worth an event, see `ServerWatcher::update`.
+    pub thresholds: Vec<u8>,
+}
+
+/// Polls every server on its own thread and keeps the results in the returned statuses,
+/// every result is also recorded in `store` when one is given.
+pub fn spawn(addresses: &[String], config: PollConfig, store: Option<Arc<Mutex<Store>>>) -> Statuses {
+    let statuses: Statuses = Arc::new(RwLock::new(
+        addresses.iter().map(|address| ServerStatus::new(address)).collect()
+    ));
+    let interval = config.interval.to_std().unwrap_or_else(|_| std::time::Duration::from_secs(1));
+
+    for (index, address) in addresses.iter().enumerate() {
+        let statuses = statuses.clone();
+        let address = address.clone();
+        let store = store.clone();
+        let PollConfig { timeout, family, decoding, with_rules, thresholds, .. } = config.clone();
+
+        thread::spawn(move || {
+            let mut server = None;
+            let mut watcher = ServerWatcher::new();
+
+            loop {
+                let result = poll(&mut server, &address, timeout, family, decoding);
+                let rules_info = match (&result, server.as_mut()) {
+                    (Ok((server_info, _, _)), Some(server)) if with_rules && server_info.answers_rules() => Some(poll_rules(server)),
+                    _ => None,
+                };
+
+                if let Some(store) = &store {
+                    if let Err(err) = record(&mut store.lock().expect("History store lock is poisoned"), &address, &result, &mut watcher, &thresholds) {
+                        eprintln!("Failed to record {}, {}", address, err);
+                    }
+                }
+
+                let mut statuses = statuses.write().expect("Server statuses lock is poisoned");
+                let status = &mut statuses[index];
+
+                match result {
+                    Ok((server_info, players_info, ping)) => {
+                        status.up = true;
+                        status.server_info = Some(server_info);
+                        status.players_info = Some(players_info);
+                        status.ping = Some(ping);
+                        status.updated_at = Some(Utc::now());
+                        status.last_error = None;
+
+                        match rules_info {
+                            Some(Ok(rules_info)) => {
+                                status.rules_info = Some(rules_info);
+                                status.rules_error = None;
+                            }
+                            Some(Err(err)) => status.rules_error = Some(err.to_string()),
+                            None => {}
+                        }
+                    }
+                    Err(err) => {
+                        status.up = false;
+                        *status.failures.entry(err.kind().to_string()).or_insert(0) += 1;
+                        status.last_error = Some(err.to_string());
+                    }
+                }
+
+                drop(statuses);
+                thread::sleep(interval);
+            }
+        });
+    }
+
+    statuses
+}
+
+type Poll = (ServerInfo, PlayersResponse, Duration);
+
+fn record(store: &mut Store, address: &str, result: &Result<Poll, QueryError>, watcher: &mut ServerWatcher, thresholds: &[u8]) -> Result<(), String> {
+    let now = Utc::now();
+
+    let events = match result {
+        Ok((server_info, players_info, ping)) => {
+            store.record_poll(address, now, server_info, players_info, *ping)?;
+            watcher.update(server_info, thresholds)
+        }
+        Err(err) => {
+            store.record_error(address, now, err.message())?;
+            watcher.failed(err.message())
+        }
+    };
+
+    store.record_events(address, now, &events)?;
+    store.prune_if_due(now)
+}
+
+fn poll(server: &mut Option<Server>, address: &str, timeout: Duration, family: AddressFamily, decoding: StringDecoding) -> Result<Poll, QueryError> {
+    if server.is_none() {
+        match Server::connect(address, family, timeout) {
+            Response::Ok(mut connected) => {
+                connected.set_decoding(decoding);
+                *server = Some(connected);
+            }
+            Response::Error(err) => return Err(err),
+        }
+    }
+
+    let connected = server.as_mut().expect("Server is connected above");
+
+    // A bug in the parsing must not take the poller down.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| connected.get_stats()));
+
+    match result {
+        Ok(Response::Ok(poll)) => Ok(poll),
+        Ok(Response::Error(err)) => Err(err),
+        Err(_) => Err(QueryError::Malformed("Failed to parse the response from the server".to_string())),
+    }
+}
+
+fn poll_rules(server: &mut Server) -> Result<RulesResponse, QueryError> {
+    match panic::catch_unwind(AssertUnwindSafe(|| server.get_rules())) {
+        Ok(Response::Ok(rules_info)) => Ok(rules_info),
+        Ok(Response::Error(err)) => Err(err),
+        Err(_) => Err(QueryError::Malformed("Failed to parse the rules from the server".to_string())),
+    }
+}
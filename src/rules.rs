@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::search::Search;
+use crate::server::RulesResponse;
+
+/// Cvars shown above all others, in this order. Plugin versions (`*_version`) follow them.
+const PINNED: [&str; 10] = [
+    "mp_timelimit",
+    "mp_maxrounds",
+    "mp_winlimit",
+    "mp_fraglimit",
+    "sv_tags",
+    "sv_password",
+    "sv_cheats",
+    "sv_gravity",
+    "mp_friendlyfire",
+    "nextlevel",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleRow {
+    pub name: String,
+    pub value: String,
+    pub pinned: bool,
+    /// The value differs from the one of the previous refresh.
+    pub changed: bool,
+}
+
+fn pin_rank(name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+
+    match PINNED.iter().position(|pinned| *pinned == name) {
+        Some(rank) => Some(rank),
+        None if name.ends_with("_version") => Some(PINNED.len()),
+        None => None,
+    }
+}
+
+/// Pinned rules first, then everything else by name. Only rules whose name or
+/// value matches `search` are kept.
+pub fn rule_rows(rules: &RulesResponse, changed: &HashSet<String>, search: Option<&Search>) -> Vec<RuleRow> {
+    let mut rows: Vec<RuleRow> = rules.rules
+        .iter()
+        .filter(|rule| search.is_none_or(|search| search.matches(&rule.name) || search.matches(&rule.value)))
+        .map(|rule| RuleRow {
+            name: rule.name.clone(),
+            value: rule.value.clone(),
+            pinned: pin_rank(&rule.name).is_some(),
+            changed: changed.contains(&rule.name),
+        })
+        .collect();
+
+    rows.sort_by_key(|row| (pin_rank(&row.name).unwrap_or(usize::MAX), row.name.to_lowercase()));
+    rows
+}
+
+/// Names of the rules whose value differs between two refreshes, new rules included.
+pub fn changed_rules(previous: &RulesResponse, current: &RulesResponse) -> HashSet<String> {
+    let previous: HashMap<&str, &str> = previous.rules
+        .iter()
+        .map(|rule| (rule.name.as_str(), rule.value.as_str()))
+        .collect();
+
+    current.rules
+        .iter()
+        .filter(|rule| previous.get(rule.name.as_str()) != Some(&rule.value.as_str()))
+        .map(|rule| rule.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Rule;
+
+    fn rules(rules: &[(&str, &str)]) -> RulesResponse {
+        RulesResponse {
+            header: 0x45,
+            rules: rules.iter().map(|(name, value)| Rule { name: name.to_string(), value: value.to_string() }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_pinned_rules_come_first() {
+        let rules = rules(&[("bot_quota", "0"), ("sm_version", "1.11"), ("sv_tags", "a,b"), ("mp_timelimit", "30")]);
+
+        let names: Vec<String> = rule_rows(&rules, &HashSet::new(), None).into_iter().map(|row| row.name).collect();
+
+        assert_eq!(vec!["mp_timelimit", "sv_tags", "sm_version", "bot_quota"], names);
+    }
+
+    #[test]
+    fn test_search_matches_names_and_values() {
+        let rules = rules(&[("bot_quota", "0"), ("sv_tags", "dust2,casual"), ("mp_timelimit", "30")]);
+        let search = Search::new("casual", false).unwrap();
+
+        let rows = rule_rows(&rules, &HashSet::new(), Some(&search));
+
+        assert_eq!(1, rows.len());
+        assert_eq!("sv_tags", rows[0].name);
+    }
+
+    #[test]
+    fn test_changed_rules() {
+        let previous = rules(&[("mp_timelimit", "30"), ("sv_tags", "a")]);
+        let current = rules(&[("mp_timelimit", "45"), ("sv_tags", "a"), ("sv_cheats", "1")]);
+
+        let mut changed: Vec<String> = changed_rules(&previous, &current).into_iter().collect();
+        changed.sort();
+
+        assert_eq!(vec!["mp_timelimit", "sv_cheats"], changed);
+    }
+}
@@ -0,0 +1,65 @@
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// A case insensitive player name filter, either a plain substring or a regex.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub text: String,
+    pub regex: bool,
+    pattern: Regex,
+}
+
+impl Search {
+    pub fn new(text: &str, regex: bool) -> Result<Self, String> {
+        let source = if regex { text.to_string() } else { regex::escape(text) };
+
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(true)
+            .build()
+            .map_err(|err| format!("Invalid regex '{}', error: {}", text, err))?;
+
+        Ok(Search { text: text.to_string(), regex, pattern })
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        self.pattern.is_match(value)
+    }
+
+    /// The byte ranges of `value` that match, to highlight them.
+    pub fn find(&self, value: &str) -> Vec<Range<usize>> {
+        self.pattern
+            .find_iter(value)
+            .map(|found| found.range())
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substring_ignores_case_and_special_chars() {
+        let search = Search::new("[CLAN]", false).unwrap();
+
+        assert!(search.matches("[clan] alice"));
+        assert!(!search.matches("clan alice"));
+    }
+
+    #[test]
+    fn test_regex() {
+        let search = Search::new("^b.b$", true).unwrap();
+
+        assert!(search.matches("Bob"));
+        assert!(!search.matches("bobby"));
+        assert!(Search::new("(", true).is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(vec![0..3, 8..11], Search::new("bob", false).unwrap().find("Bob and bob"));
+        assert_eq!(vec![2..5], Search::new("é.", true).unwrap().find("a éa"));
+        assert!(Search::new("x*", true).unwrap().find("abc").is_empty());
+    }
+}
@@ -1,18 +1,122 @@
-use crate::bytereader::ByteReader;
+use crate::bytereader::{ByteReader, StringDecoding};
 use crate::constants;
+use crate::games::{self, Game, Quirk, SplitHeader};
+use crate::steamid::SteamId;
 use chrono::Duration;
-use std::net::UdpSocket;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::time::Instant;
 
 pub enum Response<T> {
     Ok(T),
-    Error(String),
+    Error(QueryError),
+}
+
+/// Why a query failed, every kind carries the message shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    /// The address could not be resolved.
+    Resolve(String),
+    /// No socket could be opened to the address.
+    Connect(String),
+    Timeout(String),
+    /// The host answered that nothing listens on the port.
+    Refused(String),
+    /// The response ended early or is not what was expected.
+    Malformed(String),
+    Unsupported(String),
+    Other(String),
+}
+
+impl QueryError {
+    /// Reading or writing the socket failed, sorted by the I/O error.
+    fn io(context: &str, err: io::Error) -> Self {
+        let message = format!("{}, error: {}", context, err);
+
+        match err.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => QueryError::Timeout(message),
+            io::ErrorKind::ConnectionRefused => QueryError::Refused(message),
+            _ => QueryError::Other(message),
+        }
+    }
+
+    /// The label the failures are counted by.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QueryError::Resolve(_)     => "resolve",
+            QueryError::Connect(_)     => "connect",
+            QueryError::Timeout(_)     => "timeout",
+            QueryError::Refused(_)     => "refused",
+            QueryError::Malformed(_)   => "bad_response",
+            QueryError::Unsupported(_) => "unsupported",
+            QueryError::Other(_)       => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            QueryError::Resolve(message)
+            | QueryError::Connect(message)
+            | QueryError::Timeout(message)
+            | QueryError::Refused(message)
+            | QueryError::Malformed(message)
+            | QueryError::Unsupported(message)
+            | QueryError::Other(message) => message,
+        }
+    }
+
+    /// The same kind of error with a new message.
+    fn with_message(self, message: String) -> Self {
+        match self {
+            QueryError::Resolve(_)     => QueryError::Resolve(message),
+            QueryError::Connect(_)     => QueryError::Connect(message),
+            QueryError::Timeout(_)     => QueryError::Timeout(message),
+            QueryError::Refused(_)     => QueryError::Refused(message),
+            QueryError::Malformed(_)   => QueryError::Malformed(message),
+            QueryError::Unsupported(_) => QueryError::Unsupported(message),
+            QueryError::Other(_)       => QueryError::Other(message),
+        }
+    }
+
+    /// Prefixes the message with what was being done, `Failed to get players, reason: …`.
+    fn context(self, context: &str) -> Self {
+        let message = format!("{}, reason: {}", context, self.message());
+        self.with_message(message)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// The errors of the byte reader, the response is shorter or otherwise not what was expected.
+impl From<String> for QueryError {
+    fn from(message: String) -> Self {
+        QueryError::Malformed(message)
+    }
 }
 
 pub struct Server {
-    socket: UdpSocket    
+    socket: UdpSocket,
+    /// Every address the server name resolved to, `current` is the one the socket is
+    /// connected to.
+    addresses: Vec<SocketAddr>,
+    current: usize,
+    timeout: std::time::Duration,
+    decoding: StringDecoding,
+    /// The app ID of the last server info, it tells how the players are laid out.
+    app_id: Option<u16>,
+    /// Time from sending the last request to its first response packet.
+    round_trip: Duration,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ServerInfo {
     pub header: u8,
     pub protocol: u8,
@@ -20,7 +124,7 @@ pub struct ServerInfo {
     pub map: String,
     pub folder: String,
     pub game: String,
-    pub id: i16,    
+    pub id: u16,
     pub players: u8,
     pub max_players: u8,
     pub bots: u8,
@@ -30,35 +134,233 @@ pub struct ServerInfo {
     pub vac: bool,
     pub ship_mode: Option<ShipMode>,
     pub witnesses: Option<u8>,
+    #[serde(serialize_with = "serialize_optional_duration")]
     pub duration: Option<chrono::Duration>,
     pub version: String,
     pub edf: Option<u8>,
-    pub port: Option<i16>,
-    pub steam_id: Option<i32>,
-    pub source_tv_port: Option<i16>,
+    pub port: Option<u16>,
+    pub steam_id: Option<SteamId>,
+    pub source_tv_port: Option<u16>,
     pub source_tv_name: Option<String>,
     pub keywords: Option<String>,
-    pub game_id: Option<i16>,
+    pub game_id: Option<u64>,
+    /// Which of the resolved addresses answered, not part of the response.
+    pub answered_by: Option<SocketAddr>,
+}
+
+impl ServerInfo {
+    /// What the registry knows about the game, `None` for unknown app IDs.
+    pub fn game(&self) -> Option<&'static Game> {
+        games::registry().get(self.id)
+    }
+
+    /// Whether servers of the game answer rules queries, assumed for unknown games.
+    pub fn answers_rules(&self) -> bool {
+        !self.game().is_some_and(|game| game.has(Quirk::NoRules))
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Player {
     pub index: u8,
     pub name: String,
-    pub score: u32,
+    /// The name as sent by the server, only serialized when it is not valid UTF-8.
+    #[serde(skip_serializing_if = "is_utf8")]
+    pub raw_name: Vec<u8>,
+    pub score: i32,
+    #[serde(serialize_with = "serialize_duration")]
     pub duration: chrono::Duration,
-    pub deaths: u32,
-    pub money: Option<u32>,
+    pub deaths: i32,
+    pub money: Option<i32>,
+}
+
+/// Reads the optional fields announced by the extra data flag (EDF) at the end of the info.
+fn read_extra_data(buf: &mut ByteReader, server_info: &mut ServerInfo) -> Result<(), String> {
+    let edf = buf.get_byte()?;
+    server_info.edf = Some(edf);
+
+    if edf & constants::EDF_PORT != 0 {
+        server_info.port = Some(buf.get_u16()?);
+    }
+    if edf & constants::EDF_STEAM_ID != 0 {
+        server_info.steam_id = Some(SteamId::from_u64(buf.get_u64()?));
+    }
+    if edf & constants::EDF_SOURCE_TV != 0 {
+        server_info.source_tv_port = Some(buf.get_u16()?);
+        server_info.source_tv_name = Some(buf.get_string()?);
+    }
+    if edf & constants::EDF_KEYWORDS != 0 {
+        server_info.keywords = Some(buf.get_string()?);
+    }
+    if edf & constants::EDF_GAME_ID != 0 {
+        server_info.game_id = Some(buf.get_u64()?);
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
+/// Reads the header of a packet of a split response up to its payload, returns the
+/// total number of packets and the number of this one.
+fn read_split_header(buf: &mut ByteReader, layout: SplitHeader) -> Result<(u8, u8), QueryError> {
+    let id = buf.get_long()?;
+
+    match layout {
+        SplitHeader::GoldSource => {
+            let packet = buf.get_byte()?;
+            Ok((packet & 0x0F, packet >> 4))
+        }
+        SplitHeader::Source | SplitHeader::SourceWithoutSize => {
+            if id & 0x8000_0000 != 0 {
+                return Err(QueryError::Unsupported("Compressed multi packet responses are not supported".to_string()));
+            }
+
+            let total = buf.get_byte()?;
+            let number = buf.get_byte()?;
+            if layout == SplitHeader::Source {
+                buf.get_u16()?; // maximum packet size, not needed
+            }
+
+            Ok((total, number))
+        }
+    }
+}
+
+fn read_rules(buf: &mut ByteReader) -> Result<RulesResponse, String> {
+    let header = buf.get_byte()?;
+    let rule_count = buf.get_u16()?;
+    let mut rules = vec![];
+
+    for _ in 0..rule_count {
+        rules.push(Rule {
+            name: buf.get_string()?,
+            value: buf.get_string()?,
+        });
+    }
+
+    Ok(RulesResponse {
+        header,
+        rules,
+    })
+}
+
+/// A2S does not flag bots, this goes by the `BOT` name prefix most Source games give them.
+pub fn looks_like_bot(name: &str) -> bool {
+    let name = name.trim_start();
+
+    name.starts_with("BOT ") || name.starts_with("[BOT]") || name.starts_with("(BOT)")
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct PlayersResponse {
     pub header: u8,
     pub players: Vec<Player>,
     pub is_ship: bool,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl PlayersResponse {
+    /// Sorts by `column` in its natural direction, see `SortColumn::descending`,
+    /// or the other way around when `reversed`.
+    pub fn sort_by(&mut self, column: SortColumn, reversed: bool) {
+        match column {
+            SortColumn::Name => self.players.sort_by_key(|player| player.name.to_lowercase()),
+            SortColumn::Score => self.players.sort_by_key(|player| std::cmp::Reverse(player.score)),
+            SortColumn::Duration => self.players.sort_by_key(|player| std::cmp::Reverse(player.duration)),
+            SortColumn::Deaths => self.players.sort_by_key(|player| std::cmp::Reverse(player.deaths)),
+            SortColumn::Money => self.players.sort_by_key(|player| std::cmp::Reverse(player.money)),
+        }
+
+        if reversed {
+            self.players.reverse();
+        }
+    }
+
+    /// The columns worth showing, deaths and money are only sent by The Ship.
+    pub fn columns(&self) -> Vec<SortColumn> {
+        if self.is_ship {
+            vec![SortColumn::Name, SortColumn::Score, SortColumn::Duration, SortColumn::Deaths, SortColumn::Money]
+        } else {
+            vec![SortColumn::Name, SortColumn::Score, SortColumn::Duration]
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SortColumn {
+    Name,
+    Score,
+    Duration,
+    Deaths,
+    Money,
+}
+
+impl SortColumn {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortColumn::Name     => "Name",
+            SortColumn::Score    => "Score",
+            SortColumn::Duration => "Duration",
+            SortColumn::Deaths   => "Deaths",
+            SortColumn::Money    => "Money",
+        }
+    }
+
+    /// Names sort alphabetically, everything else highest first.
+    pub fn descending(&self) -> bool {
+        *self != SortColumn::Name
+    }
+
+    /// The next column out of `columns`, wrapping around.
+    pub fn next(&self, columns: &[SortColumn]) -> Self {
+        match columns.iter().position(|column| column == self) {
+            Some(index) => columns[(index + 1) % columns.len()],
+            None => columns.first().copied().unwrap_or(SortColumn::Name),
+        }
+    }
+}
+
+impl FromStr for SortColumn {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "name"     => Ok(Self::Name),
+            "score"    => Ok(Self::Score),
+            "duration" => Ok(Self::Duration),
+            "deaths"   => Ok(Self::Deaths),
+            "money"    => Ok(Self::Money),
+            _          => Err(format!("Unknown sort column '{}', expected name, score, duration, deaths or money", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Rule {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesResponse {
+    pub header: u8,
+    pub rules: Vec<Rule>,
+}
+
+fn is_utf8(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_ok()
+}
+
+fn serialize_duration<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+fn serialize_optional_duration<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_duration(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
 pub enum ServerType {
     Dedicated,
     NonDedicated,
@@ -76,9 +378,18 @@ impl ServerType {
             _       => Self::Unkown,
         }
     }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Dedicated     => "Dedicated",
+            Self::NonDedicated  => "Non-dedicated",
+            Self::SourceTvRelay => "SourceTV relay",
+            Self::Unkown        => "Unknown",
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize)]
 pub enum ShipMode {
     Hunt,
     Elimination,
@@ -101,9 +412,21 @@ impl ShipMode {
             _    => Self::Unknown,
         }
     }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Hunt            => "Hunt",
+            Self::Elimination     => "Elimination",
+            Self::Duel            => "Duel",
+            Self::Deathmatch      => "Deathmatch",
+            Self::VipTeam         => "VIP Team",
+            Self::TeamElimination => "Team Elimination",
+            Self::Unknown         => "Unknown",
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize)]
 pub enum Environment {
     Linux,
     Windows,
@@ -120,10 +443,18 @@ impl Environment {
             _           => Self::Unknown,            
         }
     }
-}
 
-#[derive(Debug, Copy, Clone)]
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Linux   => "Linux",
+            Self::Windows => "Windows",
+            Self::Mac     => "macOS",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
 
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub enum ServerVisibility {
     Public,
     Private,
@@ -138,139 +469,762 @@ impl ServerVisibility {
             _    => Self::Unknown,
         }
     }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Public  => "Public",
+            Self::Private => "Password protected",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Which addresses of a server name are queried.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum AddressFamily {
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    fn matches(&self, address: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Any  => true,
+            AddressFamily::Ipv4 => address.is_ipv4(),
+            AddressFamily::Ipv6 => address.is_ipv6(),
+        }
+    }
+}
+
+impl FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "any"         => Ok(AddressFamily::Any),
+            "ipv4" | "4"  => Ok(AddressFamily::Ipv4),
+            "ipv6" | "6"  => Ok(AddressFamily::Ipv6),
+            _             => Err(format!("Unknown address family '{}', expected any, ipv4 or ipv6", value)),
+        }
+    }
+}
+
+/// Every address of `address` of `family`, in the order of the resolver. `address` is a
+/// host name or IP with an optional port, IPv6 addresses with a port go in brackets
+/// (`[::1]:27015`).
+pub fn resolve(address: &str, family: AddressFamily) -> Result<Vec<SocketAddr>, String> {
+    let address = address.trim();
+    let resolved = match address.parse::<IpAddr>() {
+        Ok(ip) => Ok(vec![SocketAddr::new(ip, constants::DEFAULT_PORT)]),
+        Err(_) if !address.contains(':') => (address, constants::DEFAULT_PORT).to_socket_addrs().map(Iterator::collect),
+        Err(_) => address.to_socket_addrs().map(Iterator::collect),
+    };
+
+    let mut addresses: Vec<SocketAddr> = vec![];
+    for resolved in resolved.map_err(|err| format!("Failed to resolve {}, error: {}", address, err))? {
+        if family.matches(&resolved) && !addresses.contains(&resolved) {
+            addresses.push(resolved);
+        }
+    }
+
+    if addresses.is_empty() {
+        let family = match family {
+            AddressFamily::Any  => "",
+            AddressFamily::Ipv4 => "IPv4 ",
+            AddressFamily::Ipv6 => "IPv6 ",
+        };
+        return Err(format!("Failed to resolve {}, error: no {}address", address, family));
+    }
+
+    Ok(addresses)
+}
+
+/// A socket of the family of `address`, connected to it.
+fn open_socket(address: SocketAddr, timeout: std::time::Duration) -> Result<UdpSocket, String> {
+    let local = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(local)
+        .map_err(|err| format!("Failed to bind a local socket, error: {}", err))?;
+
+    socket
+        .set_write_timeout(Some(timeout))
+        .expect("Failed to set write timeout");
+
+    socket
+        .set_read_timeout(Some(timeout))
+        .expect("Failed to set read timeout");
+
+    socket
+        .connect(address)
+        .map_err(|err| format!("Failed to connect to {}, error: {}", address, err))?;
+
+    Ok(socket)
 }
 
 impl Server {
-    pub fn connect(ip: &str) -> Self {
-        let socket = UdpSocket::bind("0.0.0.0:8899")
-            .expect(&format!("Failed to connect to {}", ip)[..]);            
-        let timout_duration = chrono::Duration::seconds(5)
-            .to_std()
-            .expect("something went wrong");
-
-        socket
-            .set_write_timeout(Some(timout_duration))
-            .expect("Failed to set write timeout");
-        
-        socket
-            .set_read_timeout(Some(timout_duration))
-            .expect("Failed to set read timeout");
-
-        socket.connect(ip).expect("");
-
-        Self {
-            socket
-        }
-    }
-
-    pub fn send(&mut self, request: &[u8]) -> Response<ByteReader> {
-        self.socket
-            .send(request)
-            .expect("Failed to send the request");
-
-            let buf = &mut [0x00; 1400].to_vec();
-            match self.socket.recv(buf) {
-                Ok(_) => {
-                    let mut reader = ByteReader::new(buf.to_vec());
-                    let header_response = reader.get_long();
-                    
-                    if header_response == constants::SIMPLE_RESPONSE_HEADER {
-                        Response::Ok(ByteReader::new(reader.peek_remaining_bytes().to_vec()))
-                    } else {
-                        Response::Error(format!("Unexpected header received from the server: {:?}", header_response))
-                    }                
-                },
-                Err(err) => Response::Error(format!("Failed to read bytes, error: {}", err))
+    /// Resolves `address` and connects to the first of its addresses of `family`, the
+    /// queries move on to the others when it does not answer.
+    pub fn connect(address: &str, family: AddressFamily, timeout: Duration) -> Response<Self> {
+        let timeout = match timeout.to_std() {
+            Ok(duration) if duration.as_millis() > 0 => duration,
+            _ => return Response::Error(QueryError::Other(format!("Invalid timeout: {}", timeout))),
+        };
+        let addresses = match resolve(address, family) {
+            Ok(addresses) => addresses,
+            Err(err) => return Response::Error(QueryError::Resolve(err)),
+        };
+
+        match open_socket(addresses[0], timeout) {
+            Ok(socket) => Response::Ok(Self {
+                socket,
+                addresses,
+                current: 0,
+                timeout,
+                decoding: StringDecoding::default(),
+                app_id: None,
+                round_trip: Duration::zero(),
+            }),
+            Err(err) => Response::Error(QueryError::Connect(err)),
+        }
+    }
+
+    /// The address the queries go to, the last one that answered.
+    pub fn peer(&self) -> SocketAddr {
+        self.addresses[self.current]
+    }
+
+    /// Runs `query` against the current address, then the other resolved ones in turn
+    /// until one answers. The error tells what went wrong with every address.
+    fn failover<T>(&mut self, mut query: impl FnMut(&mut Self) -> Response<T>) -> Response<T> {
+        let mut errors = vec![];
+        let first = self.current;
+
+        for attempt in 0..self.addresses.len() {
+            let index = (first + attempt) % self.addresses.len();
+
+            if index != self.current {
+                match open_socket(self.addresses[index], self.timeout) {
+                    Ok(socket) => {
+                        self.socket = socket;
+                        self.current = index;
+                    }
+                    Err(err) => {
+                        errors.push(QueryError::Connect(err));
+                        continue;
+                    }
+                }
+            }
+
+            match query(self) {
+                Response::Ok(value) => return Response::Ok(value),
+                Response::Error(err) if self.addresses.len() == 1 => return Response::Error(err),
+                Response::Error(err) => {
+                    let message = format!("{}: {}", self.peer(), err);
+                    errors.push(err.with_message(message));
+                }
+            }
+        }
+
+        // Counted as the failure of the last address tried.
+        let message = errors.iter().map(QueryError::message).collect::<Vec<_>>().join("; ");
+        match errors.pop() {
+            Some(err) => Response::Error(err.with_message(format!("No address answered, {}", message))),
+            None => Response::Error(QueryError::Other("No address to query".to_string())),
+        }
+    }
+
+    /// How the strings of the following responses are decoded.
+    pub fn set_decoding(&mut self, decoding: StringDecoding) {
+        self.decoding = decoding;
+    }
+
+    fn send(&mut self, request: &[u8]) -> Result<ByteReader, QueryError> {
+        if let Err(err) = self.socket.send(request) {
+            return Err(QueryError::io("Failed to send the request", err));
+        }
+
+        let started = Instant::now();
+        let mut reader = self.receive()?;
+        self.round_trip = Duration::from_std(started.elapsed()).unwrap_or_else(|_| Duration::zero());
+        let header_response = reader.get_long()?;
+
+        if header_response == constants::SIMPLE_RESPONSE_HEADER {
+            Ok(ByteReader::new(reader.peek_remaining_bytes()?.to_vec()).with_decoding(self.decoding))
+        } else if header_response == constants::MULTI_PACKET_RESPONSE_HEADER {
+            self.receive_multi_packet(reader)
+        } else {
+            Err(QueryError::Malformed(format!("Unexpected header received from the server: {:?}", header_response)))
+        }
+    }
+
+    fn receive(&mut self) -> Result<ByteReader, QueryError> {
+        let buf = &mut [0x00; 1400];
+        match self.socket.recv(buf) {
+            Ok(size) => Ok(ByteReader::new(buf[..size].to_vec())),
+            Err(err) => Err(QueryError::io("Failed to read bytes", err))
+        }
+    }
+
+    /// Collects every packet of a split response and returns the reassembled payload.
+    /// `first` is the already received packet, positioned right after its multi packet
+    /// header. The packet headers are laid out by the engine of the last server info.
+    fn receive_multi_packet(&mut self, first: ByteReader) -> Result<ByteReader, QueryError> {
+        let layout = self.app_id
+            .and_then(|app_id| games::registry().get(app_id))
+            .map_or(SplitHeader::Source, Game::split_header);
+        let mut packets = BTreeMap::new();
+        let mut reader = first;
+
+        loop {
+            let (total, number) = read_split_header(&mut reader, layout)?;
+
+            packets.insert(number, reader.peek_remaining_bytes()?.to_vec());
+
+            if packets.len() >= total as usize {
+                break;
+            }
+
+            reader = self.receive()
+                .map_err(|err| err.context("Failed to receive the rest of a multi packet response"))?;
+
+            if reader.get_long()? != constants::MULTI_PACKET_RESPONSE_HEADER {
+                return Err(QueryError::Malformed("Unexpected packet received in a multi packet response".to_string()));
             }
+        }
+
+        let mut payload = ByteReader::new(packets.into_values().flatten().collect());
+
+        if payload.get_long()? == constants::SIMPLE_RESPONSE_HEADER {
+            Ok(ByteReader::new(payload.peek_remaining_bytes()?.to_vec()).with_decoding(self.decoding))
+        } else {
+            Err(QueryError::Malformed("Unexpected header in a reassembled multi packet response".to_string()))
+        }
+    }
+
+    /// Sends a request that has to be repeated with the challenge number handed
+    /// out by the server, and returns the response to the repeated request.
+    fn send_with_challenge(&mut self, mut request: [u8; 9]) -> Result<ByteReader, QueryError> {
+        let mut buf = self.send(&request)
+            .map_err(|err| err.context("Failed to send inital challenge request"))?;
+
+        if buf.get_byte()? != constants::CHALLANGE_RESPONSE {
+            return Err(QueryError::Malformed("Expected a challenge number from the server".to_string()));
+        }
+
+        request[5] = buf.get_byte()?;
+        request[6] = buf.get_byte()?;
+        request[7] = buf.get_byte()?;
+        request[8] = buf.get_byte()?;
+
+        self.send(&request)
+            .map_err(|err| err.context("Failed to send second challenge request"))
+    }
+
+    /// Servers updated since late 2020 answer an info request with a challenge number
+    /// first, the request is then repeated with the number appended.
+    fn send_info_request(&mut self) -> Result<ByteReader, QueryError> {
+        let mut buf = self.send(&constants::SERVER_INFO_REQUEST)?;
+
+        if buf.peek_remaining_bytes()?.first() != Some(&constants::CHALLANGE_RESPONSE) {
+            return Ok(buf);
+        }
+
+        buf.get_byte()?;
+        let mut request = constants::SERVER_INFO_REQUEST.to_vec();
+        request.extend_from_slice(&buf.get_long()?.to_le_bytes());
+
+        self.send(&request)
+            .map_err(|err| err.context("Failed to send the info request with the challenge"))
+    }
+
+    /// Queries the server info when the game is not known yet, the players and split
+    /// responses are laid out by it. A failure leaves the plain protocol layout.
+    fn identify(&mut self) {
+        if self.app_id.is_none() {
+            let _ = self.get_server_info();
+        }
     }
 
     pub fn get_server_info(&mut self) -> Response<ServerInfo> {
-        match self.send(&constants::SERVER_INFO_REQUEST) {
-            Response::Error(reason) => Response::Error(format!("Failed to get server info, reason: {}", reason)),
-            Response::Ok(mut buf) => {
-                let server_info = ServerInfo { 
-                    header: buf.get_byte(),
-                    protocol: buf.get_byte(),
-                    name: buf.get_string(),
-                    map: buf.get_string(),
-                    folder: buf.get_string(),
-                    game: buf.get_string(),
-                    id: buf.get_short(),
-                    players: buf.get_byte(),
-                    max_players: buf.get_byte(),
-                    bots: buf.get_byte(),
-                    server_type: ServerType::from_byte(buf.get_byte()),
-                    environment: Environment::from_byte(buf.get_byte()),
-                    server_visibility: ServerVisibility::from_byte(buf.get_byte()),
-                    vac: buf.get_byte() == 0x01,
-                    ship_mode: None,
-                    witnesses: None,
-                    duration: None,
-                    version: "".to_string(),
-                    edf: None,
-                    port: None,
-                    steam_id: None,
-                    source_tv_port: None,
-                    source_tv_name: None,                    
-                    game_id: None,
-                    keywords: None,
-
-                };
-                if server_info.id == 2400 {
-                    Response::Ok(ServerInfo { 
-                        ship_mode: Some(ShipMode::from_byte(buf.get_byte())),
-                        witnesses: Some(buf.get_byte()),
-                        duration: Some(Duration::seconds(buf.get_byte() as i64)),
-                        version: buf.get_string(),
-                        .. server_info 
-                    })
-                } else {                   
-                    Response::Ok(ServerInfo { version: buf.get_string(), .. server_info })
-                }
+        self.failover(Self::query_server_info)
+    }
+
+    fn query_server_info(&mut self) -> Response<ServerInfo> {
+        let result = self.send_info_request()
+            .and_then(|mut buf| self.read_server_info(&mut buf).map_err(QueryError::Malformed));
+
+        match result {
+            Ok(server_info) => Response::Ok(server_info),
+            Err(err) => Response::Error(err.context("Failed to get server info")),
+        }
+    }
+
+    fn read_server_info(&mut self, buf: &mut ByteReader) -> Result<ServerInfo, String> {
+        let server_info = ServerInfo { 
+            header: buf.get_byte()?,
+            protocol: buf.get_byte()?,
+            name: buf.get_string()?,
+            map: buf.get_string()?,
+            folder: buf.get_string()?,
+            game: buf.get_string()?,
+            id: buf.get_u16()?,
+            players: buf.get_byte()?,
+            max_players: buf.get_byte()?,
+            bots: buf.get_byte()?,
+            server_type: ServerType::from_byte(buf.get_byte()?),
+            environment: Environment::from_byte(buf.get_byte()?),
+            server_visibility: ServerVisibility::from_byte(buf.get_byte()?),
+            vac: buf.get_byte()? == 0x01,
+            ship_mode: None,
+            witnesses: None,
+            duration: None,
+            version: "".to_string(),
+            edf: None,
+            port: None,
+            steam_id: None,
+            source_tv_port: None,
+            source_tv_name: None,
+            game_id: None,
+            keywords: None,
+            answered_by: Some(self.peer()),
+        };
+        let mut server_info = if games::registry().has(server_info.id, Quirk::ShipInfo) {
+            ServerInfo { 
+                ship_mode: Some(ShipMode::from_byte(buf.get_byte()?)),
+                witnesses: Some(buf.get_byte()?),
+                duration: Some(Duration::seconds(buf.get_byte()? as i64)),
+                version: buf.get_string()?,
+                .. server_info 
             }
-        }        
+        } else {                   
+            ServerInfo { version: buf.get_string()?, .. server_info }
+        };
+
+        if buf.remaining() > 0 {
+            read_extra_data(buf, &mut server_info)?;
+        }
+        self.app_id = Some(server_info.id);
+
+        Ok(server_info)
     }
+
     pub fn get_players(&mut self) -> Response<PlayersResponse> {
-        let mut request = constants::PLAYERS_CHALLANGE_RESPONSE.clone();
-
-        match self.send(&request) {
-            Response::Error(reason) => Response::Error(format!("Failed to send inital player challenge request, reason: {}", reason)),
-            Response::Ok(mut buf) => {
-                buf.get_byte(); // ignore header
-                
-                request[5] = buf.get_byte();
-                request[6] = buf.get_byte();
-                request[7] = buf.get_byte();
-                request[8] = buf.get_byte();
-
-                match self.send(&request) {
-                    Response::Error(reason) => Response::Error(format!("Failed to send second player challenge request, reason: {}", reason)),
-                    Response::Ok(mut buf) => {
-                        let header = buf.get_byte();
-                        let player_count = buf.get_byte();
-                        let mut players = vec![];
-
-                        for _ in 0..player_count {
-                            players.push(Player {
-                                index: buf.get_byte(),
-                                name: buf.get_string(),
-                                score: buf.get_long(),                                
-                                deaths: 0,
-                                duration: Duration::seconds(buf.get_float() as i64),                                
-                                money: None,
-                            });
-                        }
-
-                        players.sort_by(|a, b| b.score.cmp(&a.score));
-
-                        Response::Ok(PlayersResponse {
-                            header,
-                            is_ship: false,
-                            players,
-                        })
-                    }
-                }                
-            },
+        self.identify();
+        self.failover(Self::query_players)
+    }
+
+    fn query_players(&mut self) -> Response<PlayersResponse> {
+        let result = self.send_with_challenge(constants::PLAYERS_CHALLANGE_RESPONSE)
+            .and_then(|mut buf| self.read_players(&mut buf).map_err(QueryError::Malformed));
+
+        match result {
+            Ok(players_info) => Response::Ok(players_info),
+            Err(err) => Response::Error(err.context("Failed to get players")),
+        }
+    }
+
+    fn read_players(&self, buf: &mut ByteReader) -> Result<PlayersResponse, String> {
+        let header = buf.get_byte()?;
+        let player_count = buf.get_byte()?;
+        let mut players = vec![];
+
+        for _ in 0..player_count {
+            let index = buf.get_byte()?;
+            let raw_name = buf.get_raw_string()?.to_vec();
+
+            players.push(Player {
+                index,
+                name: buf.decode(&raw_name)?,
+                raw_name,
+                score: buf.get_int()?,
+                deaths: 0,
+                duration: Duration::seconds(buf.get_float()? as i64),
+                money: None,
+            });
+        }
+
+        // The Ship appends the deaths and money of every player.
+        let is_ship = self.app_id
+            .and_then(|app_id| games::registry().get(app_id))
+            .is_some_and(Game::extra_player_fields);
+        if is_ship {
+            for player in players.iter_mut() {
+                player.deaths = buf.get_int()?;
+                player.money = Some(buf.get_int()?);
+            }
+        }
+
+        players.sort_by_key(|player| std::cmp::Reverse(player.score));
+
+        Ok(PlayersResponse {
+            header,
+            is_ship,
+            players,
+        })
+    }
+
+    pub fn get_rules(&mut self) -> Response<RulesResponse> {
+        self.identify();
+        self.failover(Self::query_rules)
+    }
+
+    fn query_rules(&mut self) -> Response<RulesResponse> {
+        let result = self.send_with_challenge(constants::RULES_CHALLANGE_REUEST)
+            .and_then(|mut buf| read_rules(&mut buf).map_err(QueryError::Malformed));
+
+        match result {
+            Ok(rules_info) => Response::Ok(rules_info),
+            Err(err) => Response::Error(err.context("Failed to get rules")),
+        }
+    }
+
+    /// Fetches the server info and players, the ping is the round trip of the info request
+    /// that answered, without the challenge and the addresses that did not answer.
+    pub fn get_stats(&mut self) -> Response<(ServerInfo, PlayersResponse, Duration)> {
+        let server_info = match self.get_server_info() {
+            Response::Ok(server_info) => server_info,
+            Response::Error(err) => return Response::Error(err),
+        };
+        let ping = self.round_trip;
+
+        match self.get_players() {
+            Response::Ok(players_info) => Response::Ok((server_info, players_info, ping)),
+            Response::Error(err) => Response::Error(err),
+        }
+    }
+
+    /// Measures the round trip of a server info request. The dedicated ping
+    /// request is deprecated and ignored by most servers.
+    pub fn ping(&mut self) -> Response<Duration> {
+        self.failover(Self::query_ping)
+    }
+
+    fn query_ping(&mut self) -> Response<Duration> {
+        let started = Instant::now();
+
+        match self.send(&constants::SERVER_INFO_REQUEST) {
+            Err(err) => Response::Error(err.context("Failed to ping the server")),
+            Ok(_) => Response::Ok(Duration::from_std(started.elapsed()).unwrap_or_else(|_| Duration::zero())),
+        }
+    }
+}
+
+/// Responses for the tests of the modules that work with them.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    pub fn server_info(map: &str, players: u8) -> ServerInfo {
+        ServerInfo {
+            header: 0x49,
+            protocol: 17,
+            name: "Test server".to_string(),
+            map: map.to_string(),
+            folder: "cstrike".to_string(),
+            game: "Counter-Strike".to_string(),
+            id: 10,
+            players,
+            max_players: 32,
+            bots: 0,
+            server_type: ServerType::Dedicated,
+            environment: Environment::Linux,
+            server_visibility: ServerVisibility::Public,
+            vac: true,
+            ship_mode: None,
+            witnesses: None,
+            duration: None,
+            version: "1.0.0.0".to_string(),
+            edf: None,
+            port: None,
+            steam_id: None,
+            source_tv_port: None,
+            source_tv_name: None,
+            answered_by: None,
+            keywords: None,
+            game_id: None,
+        }
+    }
+
+    /// Players by name, score and seconds on the server.
+    pub fn players(players: &[(&str, i32, i64)]) -> PlayersResponse {
+        PlayersResponse {
+            header: 0x44,
+            players: players
+                .iter()
+                .map(|&(name, score, seconds)| Player {
+                    index: 0,
+                    name: name.to_string(),
+                    raw_name: name.as_bytes().to_vec(),
+                    score,
+                    duration: Duration::seconds(seconds),
+                    deaths: 0,
+                    money: None,
+                })
+                .collect(),
+            is_ship: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::server_info;
+
+    /// Players of The Ship by name, score and deaths.
+    fn players(players: &[(&str, i32, i32)]) -> PlayersResponse {
+        let mut players_info = fixtures::players(&players.iter().map(|&(name, score, _)| (name, score, 60)).collect::<Vec<_>>());
+        players_info.is_ship = true;
+
+        for (player, &(_, _, deaths)) in players_info.players.iter_mut().zip(players) {
+            player.deaths = deaths;
         }
+
+        players_info
+    }
+
+    fn names(players_info: &PlayersResponse) -> Vec<&str> {
+        players_info.players.iter().map(|player| player.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_by_column_and_direction() {
+        let mut players_info = players(&[("bob", 5, 1), ("Alice", 9, 7), ("carol", -1, 3)]);
+
+        players_info.sort_by(SortColumn::Name, false);
+        assert_eq!(vec!["Alice", "bob", "carol"], names(&players_info));
+
+        players_info.sort_by(SortColumn::Score, false);
+        assert_eq!(vec!["Alice", "bob", "carol"], names(&players_info));
+
+        players_info.sort_by(SortColumn::Deaths, true);
+        assert_eq!(vec!["bob", "carol", "Alice"], names(&players_info));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_next_sort_column_wraps_around() {
+        let columns = players(&[]).columns();
+
+        assert_eq!(SortColumn::Deaths, SortColumn::Duration.next(&columns));
+        assert_eq!(SortColumn::Name, SortColumn::Money.next(&columns));
+        assert_eq!(SortColumn::Name, SortColumn::Money.next(&columns[..3]));
+    }
+
+    #[test]
+    fn test_extra_data() {
+        let mut data = vec![0xF1];
+        data.extend_from_slice(&40000u16.to_le_bytes());
+        data.extend_from_slice(&85568392920162880u64.to_le_bytes());
+        data.extend_from_slice(&40001u16.to_le_bytes());
+        data.extend_from_slice(b"SourceTV\0");
+        data.extend_from_slice(b"cp,valve\0");
+        data.extend_from_slice(&440u64.to_le_bytes());
+        let mut buf = ByteReader::new(data);
+        let mut server_info = server_info("de_dust2", 0);
+
+        read_extra_data(&mut buf, &mut server_info).unwrap();
+
+        assert_eq!(Some(0xF1), server_info.edf);
+        assert_eq!(Some(40000), server_info.port);
+        assert_eq!(Some(SteamId::from_u64(85568392920162880)), server_info.steam_id);
+        assert!(server_info.steam_id.is_some_and(|steam_id| steam_id.is_persistent()));
+        assert_eq!(Some(40001), server_info.source_tv_port);
+        assert_eq!(Some("SourceTV".to_string()), server_info.source_tv_name);
+        assert_eq!(Some("cp,valve".to_string()), server_info.keywords);
+        assert_eq!(Some(440), server_info.game_id);
+        assert_eq!(0, buf.remaining());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Ok(vec!["127.0.0.1:27015".parse().unwrap()]), resolve("127.0.0.1", AddressFamily::Any));
+        assert_eq!(Ok(vec!["[::1]:27016".parse().unwrap()]), resolve("[::1]:27016", AddressFamily::Ipv6));
+        assert_eq!(Ok(vec!["[::1]:27015".parse().unwrap()]), resolve("::1", AddressFamily::Any));
+        assert!(resolve("[::1]:27016", AddressFamily::Ipv4).is_err());
+        assert_eq!(Ok(AddressFamily::Ipv6), "IPv6".parse());
+        assert!("ipx".parse::<AddressFamily>().is_err());
+    }
+
+    #[test]
+    fn test_failover_to_the_address_that_answers() {
+        let answering = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let answering_address = answering.local_addr().unwrap();
+        // Nothing listens on the port of a dropped socket, the query is refused.
+        let refused_address = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut request = [0; 64];
+            let (_, client) = answering.recv_from(&mut request).unwrap();
+            let mut response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 17];
+            response.extend_from_slice(b"Test server\0cp_dustbowl\0tf\0Team Fortress\0");
+            response.extend_from_slice(&440u16.to_le_bytes());
+            response.extend_from_slice(&[0, 24, 0, b'd', b'l', 0, 1]);
+            response.extend_from_slice(b"1.0\0");
+            answering.send_to(&response, client).unwrap();
+        });
+
+        let timeout = std::time::Duration::from_secs(2);
+        let mut server = Server {
+            socket: open_socket(refused_address, timeout).unwrap(),
+            addresses: vec![refused_address, answering_address],
+            current: 0,
+            timeout,
+            decoding: StringDecoding::default(),
+            app_id: None,
+            round_trip: Duration::zero(),
+        };
+
+        match server.get_server_info() {
+            Response::Ok(server_info) => assert_eq!(Some(answering_address), server_info.answered_by),
+            Response::Error(err) => panic!("{}", err),
+        }
+        assert_eq!(answering_address, server.peer());
+    }
+
+    #[test]
+    fn test_truncated_server_info_is_an_error() {
+        let answering = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = answering.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut request = [0; 64];
+            let (_, client) = answering.recv_from(&mut request).unwrap();
+            // Cut off in the middle of the map name.
+            let mut response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 17];
+            response.extend_from_slice(b"Test server\0cp_dust");
+            answering.send_to(&response, client).unwrap();
+        });
+
+        let timeout = std::time::Duration::from_secs(2);
+        let mut server = Server {
+            socket: open_socket(address, timeout).unwrap(),
+            addresses: vec![address],
+            current: 0,
+            timeout,
+            decoding: StringDecoding::default(),
+            app_id: None,
+            round_trip: Duration::zero(),
+        };
+
+        match server.get_server_info() {
+            Response::Ok(_) => panic!("a truncated response was parsed"),
+            Response::Error(err) => {
+                assert_eq!("bad_response", err.kind());
+                assert!(err.message().contains("Unexpected end of the response"), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let refused_address = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut server = match Server::connect(&refused_address.to_string(), AddressFamily::Any, Duration::seconds(2)) {
+            Response::Ok(server) => server,
+            Response::Error(err) => panic!("{}", err),
+        };
+
+        match server.get_server_info() {
+            Response::Ok(_) => panic!("nothing listens on {}", refused_address),
+            Response::Error(err) => assert_eq!("refused", err.kind()),
+        }
+
+        match Server::connect("[::1]:27015", AddressFamily::Ipv4, Duration::seconds(2)) {
+            Response::Ok(_) => panic!("an IPv6 address was connected to as IPv4"),
+            Response::Error(err) => assert_eq!(QueryError::Resolve("Failed to resolve [::1]:27015, error: no IPv4 address".to_string()), err),
+        }
+    }
+
+    #[test]
+    fn test_server_info_challenge() {
+        let answering = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = answering.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut request = [0; 64];
+            let (size, client) = answering.recv_from(&mut request).unwrap();
+            assert_eq!(&constants::SERVER_INFO_REQUEST[..], &request[..size]);
+            answering.send_to(&[0xFF, 0xFF, 0xFF, 0xFF, 0x41, 1, 2, 3, 4], client).unwrap();
+
+            let (size, client) = answering.recv_from(&mut request).unwrap();
+            assert_eq!(&[1, 2, 3, 4], &request[size - 4..size]);
+            let mut response = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 17];
+            response.extend_from_slice(b"Test server\0cp_dustbowl\0tf\0Team Fortress\0");
+            response.extend_from_slice(&440u16.to_le_bytes());
+            response.extend_from_slice(&[0, 24, 0, b'd', b'l', 0, 1]);
+            response.extend_from_slice(b"1.0\0");
+            answering.send_to(&response, client).unwrap();
+        });
+
+        let timeout = std::time::Duration::from_secs(2);
+        let mut server = Server {
+            socket: open_socket(address, timeout).unwrap(),
+            addresses: vec![address],
+            current: 0,
+            timeout,
+            decoding: StringDecoding::default(),
+            app_id: None,
+            round_trip: Duration::zero(),
+        };
+
+        match server.get_server_info() {
+            Response::Ok(server_info) => assert_eq!("Test server", server_info.name),
+            Response::Error(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_split_headers() {
+        let mut buf = ByteReader::new(vec![7, 0, 0, 0, 0x12, 0xAA]);
+        assert_eq!(Ok((2, 1)), read_split_header(&mut buf, SplitHeader::GoldSource));
+        assert_eq!(1, buf.remaining());
+
+        let mut buf = ByteReader::new(vec![7, 0, 0, 0, 2, 1, 0xE0, 0x04, 0xAA]);
+        assert_eq!(Ok((2, 1)), read_split_header(&mut buf, SplitHeader::Source));
+        assert_eq!(1, buf.remaining());
+
+        let mut buf = ByteReader::new(vec![7, 0, 0, 0, 2, 1, 0xAA]);
+        assert_eq!(Ok((2, 1)), read_split_header(&mut buf, SplitHeader::SourceWithoutSize));
+        assert_eq!(1, buf.remaining());
+
+        let mut buf = ByteReader::new(vec![7, 0, 0, 0x80, 2, 1, 0xE0, 0x04]);
+        assert_eq!("unsupported", read_split_header(&mut buf, SplitHeader::Source).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_extra_player_fields_only_for_the_ship() {
+        let address = "127.0.0.1:27015".parse().unwrap();
+        let mut server = Server {
+            socket: open_socket(address, std::time::Duration::from_secs(1)).unwrap(),
+            addresses: vec![address],
+            current: 0,
+            timeout: std::time::Duration::from_secs(1),
+            decoding: StringDecoding::default(),
+            app_id: None,
+            round_trip: Duration::zero(),
+        };
+        let mut response = vec![0x44, 1, 0];
+        response.extend_from_slice(b"alice\0");
+        response.extend_from_slice(&5i32.to_le_bytes());
+        response.extend_from_slice(&60f32.to_le_bytes());
+        response.extend_from_slice(&(-2i32).to_le_bytes());
+        response.extend_from_slice(&1500i32.to_le_bytes());
+
+        let players_info = server.read_players(&mut ByteReader::new(response.clone())).unwrap();
+        assert!(!players_info.is_ship);
+        assert_eq!(0, players_info.players[0].deaths);
+
+        server.app_id = Some(2400);
+        let players_info = server.read_players(&mut ByteReader::new(response)).unwrap();
+        assert!(players_info.is_ship);
+        assert_eq!(-2, players_info.players[0].deaths);
+        assert_eq!(Some(1500), players_info.players[0].money);
+    }
+
+    #[test]
+    fn test_looks_like_bot() {
+        assert!(looks_like_bot("BOT Gabe"));
+        assert!(looks_like_bot("[BOT] Heavy"));
+        assert!(!looks_like_bot("BOTanist"));
+    }
+}
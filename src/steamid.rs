@@ -0,0 +1,301 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccountType {
+    Invalid,
+    Individual,
+    Multiseat,
+    GameServer,
+    AnonGameServer,
+    Pending,
+    ContentServer,
+    Clan,
+    Chat,
+    ConsoleUser,
+    AnonUser,
+}
+
+impl AccountType {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            1  => Self::Individual,
+            2  => Self::Multiseat,
+            3  => Self::GameServer,
+            4  => Self::AnonGameServer,
+            5  => Self::Pending,
+            6  => Self::ContentServer,
+            7  => Self::Clan,
+            8  => Self::Chat,
+            9  => Self::ConsoleUser,
+            10 => Self::AnonUser,
+            _  => Self::Invalid,
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self {
+            Self::Invalid        => 0,
+            Self::Individual     => 1,
+            Self::Multiseat      => 2,
+            Self::GameServer     => 3,
+            Self::AnonGameServer => 4,
+            Self::Pending        => 5,
+            Self::ContentServer  => 6,
+            Self::Clan           => 7,
+            Self::Chat           => 8,
+            Self::ConsoleUser    => 9,
+            Self::AnonUser       => 10,
+        }
+    }
+
+    /// The letter of the type in a SteamID3.
+    fn letter(&self) -> char {
+        match self {
+            Self::Invalid        => 'I',
+            Self::Individual     => 'U',
+            Self::Multiseat      => 'M',
+            Self::GameServer     => 'G',
+            Self::AnonGameServer => 'A',
+            Self::Pending        => 'P',
+            Self::ContentServer  => 'C',
+            Self::Clan           => 'g',
+            Self::Chat           => 'T',
+            Self::ConsoleUser    => 'I',
+            Self::AnonUser       => 'a',
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'I' => Some(Self::Invalid),
+            'U' => Some(Self::Individual),
+            'M' => Some(Self::Multiseat),
+            'G' => Some(Self::GameServer),
+            'A' => Some(Self::AnonGameServer),
+            'P' => Some(Self::Pending),
+            'C' => Some(Self::ContentServer),
+            'g' => Some(Self::Clan),
+            'T' | 'L' | 'c' => Some(Self::Chat),
+            'a' => Some(Self::AnonUser),
+            _   => None,
+        }
+    }
+}
+
+/// How long the SteamID of a game server lasts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameServerKind {
+    /// Logged in with a game server login token, the SteamID stays the same across
+    /// restarts and address changes.
+    Persistent,
+    /// Logged in anonymously, a new SteamID is handed out on every start.
+    Anonymous,
+}
+
+impl GameServerKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameServerKind::Persistent => "Persistent (login token)",
+            GameServerKind::Anonymous  => "Anonymous (changes on restart)",
+        }
+    }
+}
+
+/// A 64 bit SteamID: the universe (8 bits), account type (4 bits), instance (20 bits)
+/// and account ID (32 bits), from the high bits to the low ones.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SteamId(u64);
+
+impl SteamId {
+    pub fn new(universe: u8, account_type: AccountType, instance: u32, account_id: u32) -> Self {
+        SteamId(
+            (universe as u64) << 56
+                | (account_type.bits() as u64 & 0xF) << 52
+                | (instance as u64 & 0xF_FFFF) << 32
+                | account_id as u64
+        )
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        SteamId(value)
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// 1 is the public universe.
+    pub fn universe(&self) -> u8 {
+        (self.0 >> 56) as u8
+    }
+
+    pub fn account_type(&self) -> AccountType {
+        AccountType::from_bits((self.0 >> 52 & 0xF) as u8)
+    }
+
+    pub fn instance(&self) -> u32 {
+        (self.0 >> 32 & 0xF_FFFF) as u32
+    }
+
+    pub fn account_id(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Whether both IDs are of the same account, whatever their type and instance. The
+    /// text forms leave those out or get them wrong for game servers.
+    pub fn same_account(&self, other: &SteamId) -> bool {
+        self.universe() == other.universe() && self.account_id() == other.account_id()
+    }
+
+    /// The legacy `STEAM_X:Y:Z` text form, `Y` is the lowest bit of the account ID.
+    pub fn steam2(&self) -> String {
+        format!("STEAM_{}:{}:{}", self.universe(), self.account_id() & 1, self.account_id() >> 1)
+    }
+
+    /// The `[G:1:N]` text form, anonymous servers and individuals outside the desktop
+    /// instance keep their instance.
+    pub fn steam3(&self) -> String {
+        let account_type = self.account_type();
+        let show_instance = match account_type {
+            AccountType::AnonGameServer => true,
+            AccountType::Individual => self.instance() != 1,
+            _ => false,
+        };
+
+        if show_instance {
+            format!("[{}:{}:{}:{}]", account_type.letter(), self.universe(), self.account_id(), self.instance())
+        } else {
+            format!("[{}:{}:{}]", account_type.letter(), self.universe(), self.account_id())
+        }
+    }
+
+    /// `None` when the SteamID is not a game server's.
+    pub fn game_server_kind(&self) -> Option<GameServerKind> {
+        match self.account_type() {
+            AccountType::GameServer => Some(GameServerKind::Persistent),
+            AccountType::AnonGameServer => Some(GameServerKind::Anonymous),
+            _ => None,
+        }
+    }
+
+    /// Whether the SteamID identifies the server across restarts and address changes.
+    pub fn is_persistent(&self) -> bool {
+        self.game_server_kind() == Some(GameServerKind::Persistent)
+    }
+}
+
+/// Shown as the SteamID64.
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_u64())
+    }
+}
+
+impl Serialize for SteamId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_u64())
+    }
+}
+
+/// Parses a SteamID64, `STEAM_X:Y:Z` or `[G:1:N]` (with an optional instance).
+impl FromStr for SteamId {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let invalid = || format!("Invalid SteamID '{}', expected a SteamID64, STEAM_X:Y:Z or [G:1:N]", value);
+
+        if let Ok(id) = value.parse::<u64>() {
+            return Ok(SteamId(id));
+        }
+
+        if let Some(rest) = value.strip_prefix("STEAM_") {
+            let parts = rest.split(':').map(str::parse::<u32>).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+
+            return match parts[..] {
+                // Old games print universe 0 for public accounts.
+                [universe, y @ 0..=1, z] if universe <= 0xFF && z <= u32::MAX >> 1 => {
+                    let universe = if universe == 0 { 1 } else { universe as u8 };
+                    Ok(SteamId::new(universe, AccountType::Individual, 1, z << 1 | y))
+                }
+                _ => Err(invalid()),
+            };
+        }
+
+        if let Some(inner) = value.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            let mut parts = inner.split(':');
+            let mut chars = parts.next().unwrap_or_default().chars();
+            let account_type = match (chars.next(), chars.next()) {
+                (Some(letter), None) => AccountType::from_letter(letter).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            };
+            let numbers = parts.map(str::parse::<u32>).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+
+            let default_instance = if account_type == AccountType::Individual { 1 } else { 0 };
+            return match numbers[..] {
+                [universe, account_id] if universe <= 0xFF => Ok(SteamId::new(universe as u8, account_type, default_instance, account_id)),
+                [universe, account_id, instance] if universe <= 0xFF && instance <= 0xF_FFFF => Ok(SteamId::new(universe as u8, account_type, instance, account_id)),
+                _ => Err(invalid()),
+            };
+        }
+
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_individual() {
+        let id = SteamId::from_u64(76561197960287930);
+
+        assert_eq!(1, id.universe());
+        assert_eq!(AccountType::Individual, id.account_type());
+        assert_eq!(1, id.instance());
+        assert_eq!(22202, id.account_id());
+        assert_eq!("STEAM_1:0:11101", id.steam2());
+        assert_eq!("[U:1:22202]", id.steam3());
+        assert_eq!(None, id.game_server_kind());
+    }
+
+    #[test]
+    fn test_game_servers() {
+        let persistent = SteamId::new(1, AccountType::GameServer, 0, 123456);
+        assert_eq!(85568392920162880, persistent.as_u64());
+        assert_eq!("[G:1:123456]", persistent.steam3());
+        assert!(persistent.is_persistent());
+
+        let anonymous = SteamId::new(1, AccountType::AnonGameServer, 77, 5000);
+        assert_eq!("[A:1:5000:77]", anonymous.steam3());
+        assert_eq!(Some(GameServerKind::Anonymous), anonymous.game_server_kind());
+        assert!(!anonymous.is_persistent());
+    }
+
+    #[test]
+    fn test_same_account() {
+        let id = SteamId::new(1, AccountType::GameServer, 1, 123456);
+
+        assert!(id.same_account(&"[G:1:123456]".parse().unwrap()));
+        assert!(!id.same_account(&"[G:1:123457]".parse().unwrap()));
+        assert!(!id.same_account(&"[G:2:123456]".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = SteamId::from_u64(76561197960287930);
+
+        assert_eq!(Ok(id), "76561197960287930".parse());
+        assert_eq!(Ok(id), "STEAM_1:0:11101".parse());
+        assert_eq!(Ok(id), "STEAM_0:0:11101".parse());
+        assert_eq!(Ok(id), "[U:1:22202]".parse());
+        assert_eq!(Ok(SteamId::new(1, AccountType::GameServer, 0, 123456)), "[G:1:123456]".parse());
+        assert_eq!(Ok(SteamId::new(1, AccountType::AnonGameServer, 77, 5000)), "[A:1:5000:77]".parse());
+        assert!("STEAM_1:2:3".parse::<SteamId>().is_err());
+        assert!("[X:1:2]".parse::<SteamId>().is_err());
+        assert!("gabe".parse::<SteamId>().is_err());
+    }
+}
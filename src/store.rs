@@ -0,0 +1,459 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::diff::{diff_players, PlayerEvent};
+use crate::server::{Player, PlayersResponse, ServerInfo};
+use crate::watcher::ServerEvent;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS servers (
+    id          INTEGER PRIMARY KEY,
+    address     TEXT NOT NULL UNIQUE,
+    name        TEXT,
+    app_id      INTEGER,
+    first_seen  INTEGER NOT NULL,
+    last_seen   INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+    id          INTEGER PRIMARY KEY,
+    server_id   INTEGER NOT NULL REFERENCES servers(id),
+    time        INTEGER NOT NULL,
+    up          INTEGER NOT NULL,
+    name        TEXT,
+    map         TEXT,
+    players     INTEGER,
+    max_players INTEGER,
+    bots        INTEGER,
+    ping_ms     INTEGER,
+    error       TEXT
+);
+CREATE INDEX IF NOT EXISTS snapshots_server_time ON snapshots (server_id, time);
+
+CREATE TABLE IF NOT EXISTS player_sessions (
+    id          INTEGER PRIMARY KEY,
+    server_id   INTEGER NOT NULL REFERENCES servers(id),
+    name        TEXT NOT NULL,
+    started_at  INTEGER NOT NULL,
+    last_seen   INTEGER NOT NULL,
+    ended_at    INTEGER,
+    score       INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS player_sessions_server_time ON player_sessions (server_id, started_at);
+
+CREATE TABLE IF NOT EXISTS server_events (
+    id          INTEGER PRIMARY KEY,
+    server_id   INTEGER NOT NULL REFERENCES servers(id),
+    time        INTEGER NOT NULL,
+    kind        TEXT NOT NULL,
+    message     TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS server_events_server_time ON server_events (server_id, time);
+
+CREATE TABLE IF NOT EXISTS map_changes (
+    id          INTEGER PRIMARY KEY,
+    server_id   INTEGER NOT NULL REFERENCES servers(id),
+    time        INTEGER NOT NULL,
+    from_map    TEXT,
+    to_map      TEXT NOT NULL
+);
+";
+
+/// The `[history]` section of `config.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Database every poll is recorded in, nothing is recorded when unset.
+    pub database: Option<PathBuf>,
+    /// Days of history to keep, 0 keeps everything.
+    pub retention_days: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            database: None,
+            retention_days: 30,
+        }
+    }
+}
+
+/// The last poll of a server, needed to turn snapshots into sessions and map changes.
+struct Previous {
+    time: DateTime<Utc>,
+    players: Vec<Player>,
+}
+
+/// How often `prune_if_due` applies the retention.
+const PRUNE_INTERVAL_HOURS: i64 = 1;
+
+/// SQLite database with every poll result, player session and map change.
+pub struct Store {
+    connection: Connection,
+    previous: HashMap<String, Previous>,
+    /// Days of history kept by `prune_if_due`, 0 keeps everything.
+    retention_days: u32,
+    pruned_at: Option<DateTime<Utc>>,
+}
+
+impl Store {
+    /// `$XDG_DATA_HOME/valve-server-reader/history.sqlite`, falling back to `~/.local/share`.
+    pub fn default_path() -> PathBuf {
+        config::data_dir().join("history.sqlite")
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("Failed to create {}, error: {}", dir.display(), err))?;
+        }
+
+        let connection = Connection::open(path)
+            .map_err(|err| format!("Failed to open {}, error: {}", path.display(), err))?;
+
+        Store::with_connection(connection)
+    }
+
+    /// Opens the database and drops what is past `retention_days`, `prune_if_due` keeps
+    /// doing so while recording.
+    pub fn open_with_retention(path: &Path, retention_days: u32) -> Result<Self, String> {
+        let mut store = Store::open(path)?;
+        store.retention_days = retention_days;
+        store.prune_if_due(Utc::now())?;
+
+        Ok(store)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, String> {
+        connection.execute_batch(SCHEMA).map_err(database_error)?;
+
+        Ok(Store {
+            connection,
+            previous: HashMap::new(),
+            retention_days: 0,
+            pruned_at: None,
+        })
+    }
+
+    /// Stores a successful poll, updating the player sessions and map changes.
+    pub fn record_poll(&mut self, address: &str, time: DateTime<Utc>, server_info: &ServerInfo, players_info: &PlayersResponse, ping: Duration) -> Result<(), String> {
+        let transaction = self.connection.transaction().map_err(database_error)?;
+        let server_id = server_id(&transaction, address, time, Some(server_info))?;
+        let now = time.timestamp();
+
+        let last_map: Option<String> = transaction
+            .query_row(
+                "SELECT map FROM snapshots WHERE server_id = ?1 AND up = 1 ORDER BY time DESC, id DESC LIMIT 1",
+                params![server_id],
+                |row| row.get(0))
+            .optional()
+            .map_err(database_error)?;
+
+        if last_map.as_deref() != Some(server_info.map.as_str()) {
+            transaction
+                .execute(
+                    "INSERT INTO map_changes (server_id, time, from_map, to_map) VALUES (?1, ?2, ?3, ?4)",
+                    params![server_id, now, last_map, server_info.map])
+                .map_err(database_error)?;
+        }
+
+        transaction
+            .execute(
+                "INSERT INTO snapshots (server_id, time, up, name, map, players, max_players, bots, ping_ms)
+                 VALUES (?1, ?2, 1, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![server_id, now, server_info.name, server_info.map, server_info.players,
+                    server_info.max_players, server_info.bots, ping.num_milliseconds()])
+            .map_err(database_error)?;
+
+        let events = match self.previous.get(address) {
+            Some(previous) => diff_players(&previous.players, &players_info.players, time - previous.time),
+            None => {
+                // Sessions left open by an earlier run cannot be continued.
+                transaction
+                    .execute(
+                        "UPDATE player_sessions SET ended_at = last_seen WHERE server_id = ?1 AND ended_at IS NULL",
+                        params![server_id])
+                    .map_err(database_error)?;
+
+                players_info.players
+                    .iter()
+                    .map(|player| PlayerEvent::Joined { name: player.name.clone(), duration: player.duration, score: player.score })
+                    .collect()
+            }
+        };
+
+        for event in events {
+            match event {
+                PlayerEvent::Joined { name, duration, score } => {
+                    let started_at = (time - duration).timestamp();
+                    transaction
+                        .execute(
+                            "INSERT INTO player_sessions (server_id, name, started_at, last_seen, score) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![server_id, name, started_at, now, score])
+                        .map_err(database_error)?;
+                }
+                PlayerEvent::Left { name, .. } => {
+                    update_open_session(&transaction, server_id, &name, "ended_at = last_seen")?;
+                }
+                PlayerEvent::Renamed { from, to } => {
+                    transaction
+                        .execute(
+                            "UPDATE player_sessions SET name = ?3 WHERE id = (
+                                SELECT id FROM player_sessions WHERE server_id = ?1 AND name = ?2 AND ended_at IS NULL ORDER BY id DESC LIMIT 1)",
+                            params![server_id, from, to])
+                        .map_err(database_error)?;
+                }
+                PlayerEvent::ScoreChanged { name, to, .. } => {
+                    update_open_session(&transaction, server_id, &name, &format!("score = {}", to))?;
+                }
+            }
+        }
+
+        transaction
+            .execute(
+                "UPDATE player_sessions SET last_seen = ?2 WHERE server_id = ?1 AND ended_at IS NULL",
+                params![server_id, now])
+            .map_err(database_error)?;
+
+        transaction.commit().map_err(database_error)?;
+
+        self.previous.insert(address.to_string(), Previous { time, players: players_info.players.clone() });
+
+        Ok(())
+    }
+
+    /// Stores a failed poll.
+    pub fn record_error(&mut self, address: &str, time: DateTime<Utc>, error: &str) -> Result<(), String> {
+        let server_id = server_id(&self.connection, address, time, None)?;
+
+        self.connection
+            .execute(
+                "INSERT INTO snapshots (server_id, time, up, error) VALUES (?1, ?2, 0, ?3)",
+                params![server_id, time.timestamp(), error])
+            .map_err(database_error)?;
+
+        Ok(())
+    }
+
+    /// Stores the events of a poll, see `ServerWatcher`.
+    pub fn record_events(&mut self, address: &str, time: DateTime<Utc>, events: &[ServerEvent]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let transaction = self.connection.transaction().map_err(database_error)?;
+        let server_id = server_id(&transaction, address, time, None)?;
+
+        for event in events {
+            transaction
+                .execute(
+                    "INSERT INTO server_events (server_id, time, kind, message) VALUES (?1, ?2, ?3, ?4)",
+                    params![server_id, time.timestamp(), event.kind(), event.to_string()])
+                .map_err(database_error)?;
+        }
+
+        transaction.commit().map_err(database_error)
+    }
+
+    /// Deletes everything older than `retention_days`, 0 keeps everything.
+    pub fn apply_retention(&self, retention_days: u32, now: DateTime<Utc>) -> Result<usize, String> {
+        if retention_days == 0 {
+            return Ok(0);
+        }
+
+        self.prune(now - Duration::days(retention_days as i64))
+    }
+
+    /// Applies the retention the store was opened with, at most once an hour. Called after
+    /// every recorded poll, long running recorders would grow without bound otherwise.
+    pub fn prune_if_due(&mut self, now: DateTime<Utc>) -> Result<(), String> {
+        if self.pruned_at.is_some_and(|pruned_at| now - pruned_at < Duration::hours(PRUNE_INTERVAL_HOURS)) {
+            return Ok(());
+        }
+
+        self.pruned_at = Some(now);
+        self.apply_retention(self.retention_days, now).map(|_| ())
+    }
+
+    /// Deletes everything older than `before`, returns the number of deleted rows.
+    pub fn prune(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        let before = before.timestamp();
+        let mut deleted = 0;
+
+        for statement in [
+            "DELETE FROM snapshots WHERE time < ?1",
+            "DELETE FROM player_sessions WHERE ended_at IS NOT NULL AND ended_at < ?1",
+            "DELETE FROM map_changes WHERE time < ?1",
+            "DELETE FROM server_events WHERE time < ?1",
+        ].iter() {
+            deleted += self.connection.execute(statement, params![before]).map_err(database_error)?;
+        }
+
+        Ok(deleted)
+    }
+}
+
+fn server_id(connection: &Connection, address: &str, time: DateTime<Utc>, server_info: Option<&ServerInfo>) -> Result<i64, String> {
+    let now = time.timestamp();
+    let (name, app_id) = match server_info {
+        Some(server_info) => (Some(server_info.name.as_str()), Some(server_info.id)),
+        None => (None, None),
+    };
+
+    connection
+        .execute(
+            "INSERT INTO servers (address, name, app_id, first_seen, last_seen) VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT (address) DO UPDATE SET
+                name = COALESCE(excluded.name, name),
+                app_id = COALESCE(excluded.app_id, app_id),
+                last_seen = excluded.last_seen",
+            params![address, name, app_id, now])
+        .map_err(database_error)?;
+
+    connection
+        .query_row("SELECT id FROM servers WHERE address = ?1", params![address], |row| row.get(0))
+        .map_err(database_error)
+}
+
+/// Applies `assignment` to the newest open session of the player.
+fn update_open_session(connection: &Connection, server_id: i64, name: &str, assignment: &str) -> Result<(), String> {
+    connection
+        .execute(
+            &format!(
+                "UPDATE player_sessions SET {} WHERE id = (
+                    SELECT id FROM player_sessions WHERE server_id = ?1 AND name = ?2 AND ended_at IS NULL ORDER BY id DESC LIMIT 1)",
+                assignment),
+            params![server_id, name])
+        .map_err(database_error)?;
+
+    Ok(())
+}
+
+fn database_error(err: rusqlite::Error) -> String {
+    format!("Database error: {}", err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fixtures::{players, server_info};
+
+    fn store() -> Store {
+        Store::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn count(store: &Store, sql: &str) -> i64 {
+        store.connection.query_row(sql, params![], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_sessions_follow_joins_and_leaves() {
+        let mut store = store();
+        let start = Utc::now();
+        let later = start + Duration::seconds(10);
+
+        store.record_poll("127.0.0.1:27015", start, &server_info("de_dust2", 2), &players(&[("alice", 1, 100), ("bob", 0, 50)]), Duration::milliseconds(20)).unwrap();
+        store.record_poll("127.0.0.1:27015", later, &server_info("de_dust2", 2), &players(&[("alice", 5, 110), ("carol", 0, 3)]), Duration::milliseconds(20)).unwrap();
+
+        let (started_at, score): (i64, i32) = store.connection
+            .query_row("SELECT started_at, score FROM player_sessions WHERE name = 'alice'", params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        let bob_ended_at: Option<i64> = store.connection
+            .query_row("SELECT ended_at FROM player_sessions WHERE name = 'bob'", params![], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!((start - Duration::seconds(100)).timestamp(), started_at);
+        assert_eq!(5, score);
+        assert_eq!(Some(start.timestamp()), bob_ended_at);
+        assert_eq!(2, count(&store, "SELECT COUNT(*) FROM player_sessions WHERE ended_at IS NULL"));
+        assert_eq!(2, count(&store, "SELECT COUNT(*) FROM snapshots"));
+    }
+
+    #[test]
+    fn test_join_with_a_taken_name() {
+        let mut store = store();
+        let start = Utc::now();
+        let later = start + Duration::seconds(10);
+
+        store.record_poll("127.0.0.1:27015", start, &server_info("de_dust2", 1), &players(&[("Player", 4, 600)]), Duration::milliseconds(20)).unwrap();
+        store.record_poll("127.0.0.1:27015", later, &server_info("de_dust2", 2), &players(&[("Player", 4, 610), ("Player", 1, 2)]), Duration::milliseconds(20)).unwrap();
+
+        let (started_at, score): (i64, i32) = store.connection
+            .query_row("SELECT started_at, score FROM player_sessions ORDER BY id DESC LIMIT 1", params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+
+        assert_eq!((later - Duration::seconds(2)).timestamp(), started_at);
+        assert_eq!(1, score);
+    }
+
+    #[test]
+    fn test_map_changes_and_errors() {
+        let mut store = store();
+        let start = Utc::now();
+
+        store.record_poll("127.0.0.1:27015", start, &server_info("de_dust2", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+        store.record_error("127.0.0.1:27015", start + Duration::seconds(10), "Failed to receive, error: timed out").unwrap();
+        store.record_poll("127.0.0.1:27015", start + Duration::seconds(20), &server_info("de_dust2", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+        store.record_poll("127.0.0.1:27015", start + Duration::seconds(30), &server_info("de_inferno", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+
+        let last_change: (Option<String>, String) = store.connection
+            .query_row("SELECT from_map, to_map FROM map_changes ORDER BY id DESC LIMIT 1", params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+
+        assert_eq!(2, count(&store, "SELECT COUNT(*) FROM map_changes"));
+        assert_eq!((Some("de_dust2".to_string()), "de_inferno".to_string()), last_change);
+        assert_eq!(1, count(&store, "SELECT COUNT(*) FROM snapshots WHERE up = 0"));
+        assert_eq!(1, count(&store, "SELECT COUNT(*) FROM servers"));
+    }
+
+    #[test]
+    fn test_server_events() {
+        let mut store = store();
+        let events = vec![ServerEvent::CameOnline, ServerEvent::VacChanged { secured: false }];
+
+        store.record_events("127.0.0.1:27015", Utc::now(), &events).unwrap();
+
+        let kinds: Vec<String> = store.connection
+            .prepare("SELECT kind FROM server_events ORDER BY id").unwrap()
+            .query_map(params![], |row| row.get(0)).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(vec!["online", "vac_changed"], kinds);
+    }
+
+    #[test]
+    fn test_retention_prunes_old_rows() {
+        let mut store = store();
+        let now = Utc::now();
+
+        store.record_poll("127.0.0.1:27015", now - Duration::days(40), &server_info("de_dust2", 1), &players(&[("alice", 1, 10)]), Duration::milliseconds(20)).unwrap();
+        store.record_poll("127.0.0.1:27015", now, &server_info("de_dust2", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+
+        assert_eq!(0, store.apply_retention(0, now).unwrap());
+        assert_eq!(3, store.apply_retention(30, now).unwrap());
+        assert_eq!(1, count(&store, "SELECT COUNT(*) FROM snapshots"));
+        assert_eq!(0, count(&store, "SELECT COUNT(*) FROM player_sessions"));
+    }
+
+    #[test]
+    fn test_prune_if_due_waits_an_hour() {
+        let mut store = store();
+        store.retention_days = 30;
+        let now = Utc::now();
+
+        store.record_poll("127.0.0.1:27015", now - Duration::days(40), &server_info("de_dust2", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+        store.prune_if_due(now).unwrap();
+        assert_eq!(0, count(&store, "SELECT COUNT(*) FROM snapshots"));
+
+        store.record_poll("127.0.0.1:27015", now - Duration::days(40), &server_info("de_dust2", 0), &players(&[]), Duration::milliseconds(20)).unwrap();
+        store.prune_if_due(now + Duration::minutes(30)).unwrap();
+        assert_eq!(1, count(&store, "SELECT COUNT(*) FROM snapshots"));
+
+        store.prune_if_due(now + Duration::hours(1)).unwrap();
+        assert_eq!(0, count(&store, "SELECT COUNT(*) FROM snapshots"));
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::games::{self, TagDialect};
+use crate::server::ServerInfo;
+
+/// Game mode tags of TF2, usually derived from the map prefix.
+const TF2_MODES: [(&str, &str); 15] = [
+    ("cp", "Control Point"),
+    ("ctf", "Capture the Flag"),
+    ("payload", "Payload"),
+    ("payloadrace", "Payload Race"),
+    ("koth", "King of the Hill"),
+    ("arena", "Arena"),
+    ("mvm", "Mann vs. Machine"),
+    ("pd", "Player Destruction"),
+    ("pass", "PASS Time"),
+    ("rd", "Robot Destruction"),
+    ("sd", "Special Delivery"),
+    ("tc", "Territorial Control"),
+    ("powerup", "Mannpower"),
+    ("medieval", "Medieval"),
+    ("halloween", "Halloween"),
+];
+
+const CS_MODES: [(&str, &str); 8] = [
+    ("competitive", "Competitive"),
+    ("casual", "Casual"),
+    ("deathmatch", "Deathmatch"),
+    ("armsrace", "Arms Race"),
+    ("demolition", "Demolition"),
+    ("wingman", "Wingman"),
+    ("dangerzone", "Danger Zone"),
+    ("retakes", "Retakes"),
+];
+
+const L4D_MODES: [(&str, &str); 7] = [
+    ("coop", "Campaign"),
+    ("realism", "Realism"),
+    ("versus", "Versus"),
+    ("teamversus", "Team Versus"),
+    ("survival", "Survival"),
+    ("scavenge", "Scavenge"),
+    ("mutation", "Mutation"),
+];
+
+/// Region tags community servers commonly put in `sv_tags`.
+const REGIONS: [(&str, &str); 12] = [
+    ("eu", "Europe"),
+    ("europe", "Europe"),
+    ("na", "North America"),
+    ("us", "North America"),
+    ("usa", "North America"),
+    ("sa", "South America"),
+    ("asia", "Asia"),
+    ("au", "Oceania"),
+    ("aus", "Oceania"),
+    ("oceania", "Oceania"),
+    ("ru", "Russia"),
+    ("africa", "Africa"),
+];
+
+/// The keywords of a server as a set, with what they mean for the game when it is known.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Tags {
+    /// Every keyword, lowercased.
+    pub tags: BTreeSet<String>,
+    pub game_mode: Option<String>,
+    pub region: Option<String>,
+    /// Run by Valve (`valve` in TF2, `valve_ds` in CS).
+    pub official: bool,
+    /// Only known for CS, official competitive servers are ranked.
+    pub ranked: Option<bool>,
+}
+
+impl Tags {
+    pub fn from_server_info(server_info: &ServerInfo) -> Self {
+        Tags::parse(server_info.id, server_info.keywords.as_deref().unwrap_or(""))
+    }
+
+    /// Interprets the comma separated `keywords` of a server of the game `app_id`, the
+    /// registry tells which game's conventions they follow.
+    pub fn parse(app_id: u16, keywords: &str) -> Self {
+        let dialect = games::registry().get(app_id).map_or(TagDialect::Plain, |game| game.tags);
+        Tags::parse_dialect(dialect, keywords)
+    }
+
+    pub fn parse_dialect(dialect: TagDialect, keywords: &str) -> Self {
+        let tags = parse_tags(keywords);
+        let modes: &[(&str, &str)] = match dialect {
+            TagDialect::Tf2 => &TF2_MODES,
+            TagDialect::CounterStrike => &CS_MODES,
+            TagDialect::Left4Dead => &L4D_MODES,
+            TagDialect::Plain => &[],
+        };
+        let lookup = |table: &[(&str, &str)]| table
+            .iter()
+            .find(|(tag, _)| tags.contains(*tag))
+            .map(|(_, name)| name.to_string());
+
+        let official = match dialect {
+            TagDialect::Tf2 => tags.contains("valve"),
+            TagDialect::CounterStrike => tags.contains("valve_ds"),
+            _ => false,
+        };
+        let ranked = match dialect {
+            TagDialect::CounterStrike => Some(official && tags.contains("competitive")),
+            _ => None,
+        };
+
+        Tags {
+            game_mode: lookup(modes),
+            region: lookup(&REGIONS),
+            official,
+            ranked,
+            tags,
+        }
+    }
+
+    /// Whether every tag of `wanted` is set, ignoring case.
+    pub fn contains_all<S: AsRef<str>>(&self, wanted: &[S]) -> bool {
+        wanted.iter().all(|tag| self.tags.contains(&tag.as_ref().trim().to_lowercase()))
+    }
+}
+
+/// Splits comma separated keywords into a set of lowercased tags, empty ones are dropped.
+pub fn parse_tags(keywords: &str) -> BTreeSet<String> {
+    keywords
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        let tags = parse_tags(" Payload,nocrits,,payload , EU");
+
+        assert_eq!(vec!["eu", "nocrits", "payload"], tags.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_known_games() {
+        let tf2 = Tags::parse(440, "cp,valve,increased_maxplayers");
+        assert_eq!(Some("Control Point".to_string()), tf2.game_mode);
+        assert!(tf2.official);
+        assert_eq!(None, tf2.ranked);
+
+        let csgo = Tags::parse(730, "secure,valve_ds,competitive,empty");
+        assert_eq!(Some("Competitive".to_string()), csgo.game_mode);
+        assert_eq!(Some(true), csgo.ranked);
+
+        let l4d2 = Tags::parse(550, "versus,eu,secure");
+        assert_eq!(Some("Versus".to_string()), l4d2.game_mode);
+        assert_eq!(Some("Europe".to_string()), l4d2.region);
+    }
+
+    #[test]
+    fn test_unknown_game_keeps_the_tags() {
+        let tags = Tags::parse(12345, "cp,valve");
+
+        assert_eq!(None, tags.game_mode);
+        assert!(!tags.official);
+        assert!(tags.contains_all(&["CP", "valve"]));
+        assert!(!tags.contains_all(&["cp", "ctf"]));
+    }
+}
@@ -0,0 +1,191 @@
+use serde::Deserialize;
+use tui::style::Color;
+
+/// The `[theme]` section of the config file. `preset` is `dark` (default) or `light`,
+/// every color overrides the one of the preset. Colors are names (`yellow`,
+/// `light_blue`, `dark_gray`, `reset`, ...), `#rrggbb` or a 256 color index.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: Option<String>,
+    pub header: Option<String>,
+    pub text: Option<String>,
+    pub selected: Option<String>,
+    pub watched: Option<String>,
+    pub bots: Option<String>,
+    pub search: Option<String>,
+    pub changed: Option<String>,
+    pub pinned: Option<String>,
+    pub error: Option<String>,
+    pub muted: Option<String>,
+    pub chart: Option<String>,
+    pub ping: Option<String>,
+}
+
+/// Colors of the interactive view.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Theme {
+    /// Table headers.
+    pub header: Color,
+    /// Player rows.
+    pub text: Color,
+    /// The selected row and tab.
+    pub selected: Color,
+    /// Players on the watchlist.
+    pub watched: Color,
+    /// Players that look like bots.
+    pub bots: Color,
+    /// Rows shown while a search is active.
+    pub search: Color,
+    /// Tabs with news and rules that changed.
+    pub changed: Color,
+    /// Pinned rules.
+    pub pinned: Color,
+    /// Errors and servers that failed.
+    pub error: Color,
+    /// Timestamps, labels, the help line and stale data.
+    pub muted: Color,
+    /// The player count chart and sparkline.
+    pub chart: Color,
+    /// The ping chart.
+    pub ping: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            header: Color::Reset,
+            text: Color::White,
+            selected: Color::Blue,
+            watched: Color::Yellow,
+            bots: Color::Gray,
+            search: Color::LightCyan,
+            changed: Color::Yellow,
+            pinned: Color::Cyan,
+            error: Color::Red,
+            muted: Color::DarkGray,
+            chart: Color::Green,
+            ping: Color::Cyan,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            header: Color::Reset,
+            text: Color::Black,
+            selected: Color::Blue,
+            watched: Color::Magenta,
+            bots: Color::DarkGray,
+            search: Color::Cyan,
+            changed: Color::Magenta,
+            pinned: Color::Blue,
+            error: Color::Red,
+            muted: Color::Gray,
+            chart: Color::Green,
+            ping: Color::Blue,
+        }
+    }
+
+    pub fn from_config(config: &ThemeConfig) -> Result<Self, String> {
+        let mut theme = match config.preset.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("dark") => Theme::dark(),
+            Some("light") => Theme::light(),
+            Some(preset) => return Err(format!("Unknown theme preset '{}', expected dark or light", preset)),
+        };
+
+        let overrides = [
+            (&config.header, &mut theme.header),
+            (&config.text, &mut theme.text),
+            (&config.selected, &mut theme.selected),
+            (&config.watched, &mut theme.watched),
+            (&config.bots, &mut theme.bots),
+            (&config.search, &mut theme.search),
+            (&config.changed, &mut theme.changed),
+            (&config.pinned, &mut theme.pinned),
+            (&config.error, &mut theme.error),
+            (&config.muted, &mut theme.muted),
+            (&config.chart, &mut theme.chart),
+            (&config.ping, &mut theme.ping),
+        ];
+
+        for (value, color) in overrides {
+            if let Some(value) = value {
+                *color = parse_color(value)?;
+            }
+        }
+
+        Ok(theme)
+    }
+}
+
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |range| hex.get(range).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+        return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+            _ => Err(format!("Invalid color '{}', expected #rrggbb", value)),
+        };
+    }
+
+    if let Ok(index) = value.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+
+    match value.to_lowercase().replace('-', "_").as_str() {
+        "reset"         => Ok(Color::Reset),
+        "black"         => Ok(Color::Black),
+        "red"           => Ok(Color::Red),
+        "green"         => Ok(Color::Green),
+        "yellow"        => Ok(Color::Yellow),
+        "blue"          => Ok(Color::Blue),
+        "magenta"       => Ok(Color::Magenta),
+        "cyan"          => Ok(Color::Cyan),
+        "gray"          => Ok(Color::Gray),
+        "dark_gray"     => Ok(Color::DarkGray),
+        "light_red"     => Ok(Color::LightRed),
+        "light_green"   => Ok(Color::LightGreen),
+        "light_yellow"  => Ok(Color::LightYellow),
+        "light_blue"    => Ok(Color::LightBlue),
+        "light_magenta" => Ok(Color::LightMagenta),
+        "light_cyan"    => Ok(Color::LightCyan),
+        "white"         => Ok(Color::White),
+        _               => Err(format!("Unknown color '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Ok(Color::DarkGray), parse_color("dark_gray"));
+        assert_eq!(Ok(Color::LightBlue), parse_color("Light-Blue"));
+        assert_eq!(Ok(Color::Rgb(0x34, 0x65, 0xa4)), parse_color("#3465a4"));
+        assert_eq!(Ok(Color::Indexed(208)), parse_color("208"));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("purple").is_err());
+    }
+
+    #[test]
+    fn test_preset_with_overrides() {
+        let config = ThemeConfig {
+            preset: Some("light".to_string()),
+            watched: Some("red".to_string()),
+            ..ThemeConfig::default()
+        };
+
+        let theme = Theme::from_config(&config).unwrap();
+
+        assert_eq!(Color::Red, theme.watched);
+        assert_eq!(Theme::light().text, theme.text);
+        assert!(Theme::from_config(&ThemeConfig { preset: Some("solarized".to_string()), ..ThemeConfig::default() }).is_err());
+    }
+}
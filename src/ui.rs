@@ -1,13 +1,602 @@
 use tui::{
     backend::Backend,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     symbols,
     text::{Span, Spans},
-    widgets::canvas::{Canvas, Line, Map, MapResolution, Rectangle},
-    widgets::{
-        Axis, BarChart, Block, Borders, Chart, Dataset, Gauge, List, ListItem,
-        Paragraph, Row, Sparkline, Table, Tabs, Wrap,
-    },
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, Paragraph, Row, Sparkline, Table, TableState, Tabs, Widget},
     Frame,
-};
\ No newline at end of file
+};
+
+use chrono::Local;
+
+use crate::app::{App, ClickAreas, StatefulTable};
+use crate::games::Quirk;
+use crate::history::TimeWindow;
+use crate::keys::Action;
+use crate::output::Tabular;
+use crate::search::Search;
+use crate::server::{looks_like_bot, ServerInfo, SortColumn};
+use crate::theme::Theme;
+use crate::watchlist::Watchlist;
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .margin(5)
+        .split(f.size());
+
+    let mut click_areas = ClickAreas {
+        tabs: draw_tabs(f, app, chunks[0]),
+        tabs_row: chunks[0].y + 1,
+        ..ClickAreas::default()
+    };
+    draw_header(f, &app.tables[app.tabs.index], app.time_window, &app.theme, chunks[1]);
+
+    let mut main = chunks[2];
+
+    if app.show_log || app.show_info {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+            .split(main);
+        let side = match (app.show_info, app.show_log) {
+            (true, true) => Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+                .split(panes[1]),
+            _ => vec![panes[1], panes[1]],
+        };
+
+        if app.show_info {
+            draw_info(f, &app.tables[app.tabs.index], &app.theme, side[0]);
+        }
+        if app.show_log {
+            draw_log(f, &app.tables[app.tabs.index], &app.theme, side[1]);
+        }
+        main = panes[0];
+    }
+
+    if app.show_chart {
+        let panes = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(main);
+
+        draw_charts(f, &app.tables[app.tabs.index], app.time_window, &app.theme, panes[1]);
+        main = panes[0];
+    }
+
+    let index = app.tabs.index;
+    if app.show_errors {
+        draw_errors(f, app, main);
+    } else if app.show_rules {
+        draw_rules(f, &mut app.tables[index], app.search.as_ref(), &app.theme, main, &mut click_areas);
+    } else {
+        draw_table(f, &mut app.tables[index], &app.watchlist, app.search.as_ref(), &app.theme, main, &mut click_areas);
+    }
+    draw_status(f, app, chunks[3]);
+
+    app.click_areas = click_areas;
+}
+
+/// Marks the selected row of a table, the other rows are indented by as many blanks.
+const HIGHLIGHT_SYMBOL: &str = ">> ";
+
+/// The area below the header of a bordered table, where its rows go.
+fn rows_area(area: Rect) -> Rect {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    let top = (inner.y + 2).min(inner.bottom());
+
+    Rect { y: top, height: inner.bottom() - top, ..inner }
+}
+
+/// Scrolls a table `height` rows high just enough to keep the selected row visible,
+/// returns the index of the first row shown.
+fn scroll(selected: Option<usize>, offset: usize, height: usize) -> usize {
+    match selected {
+        Some(selected) if selected < offset => selected,
+        Some(selected) if selected >= offset + height.max(1) => selected + 1 - height.max(1),
+        Some(_) => offset,
+        None => 0,
+    }
+}
+
+/// The width left for the columns of a bordered table, without the borders and the
+/// space between the columns.
+fn available_width(area_width: u16, columns: usize) -> u16 {
+    area_width.saturating_sub(2 + columns.saturating_sub(1) as u16)
+}
+
+/// The width of every column of a bordered table: the first column takes what the `fixed`
+/// columns after it leave, columns that do not fit are narrowed from the right.
+fn column_widths(area_width: u16, fixed: &[u16]) -> Vec<u16> {
+    let mut remaining = available_width(area_width, fixed.len() + 1);
+    let first = remaining.saturating_sub(fixed.iter().fold(0u16, |sum, width| sum.saturating_add(*width)));
+    remaining -= first;
+
+    let mut widths = vec![first];
+    for width in fixed {
+        let width = (*width).min(remaining);
+        remaining -= width;
+        widths.push(width);
+    }
+
+    widths
+}
+
+/// The rows of a table that fit below its header, and the selection among them. The table
+/// is scrolled here rather than by tui, so clicks can be mapped back to a row.
+fn visible_rows<T>(rows: impl Iterator<Item = T>, selected: Option<usize>, first: usize, rows_area: Rect) -> (Vec<T>, TableState) {
+    let mut state = TableState::default();
+    state.select(selected.map(|selected| selected - first));
+
+    (rows.skip(first).take(rows_area.height as usize).collect(), state)
+}
+
+fn draw_log<B: Backend>(f: &mut Frame<B>, table: &StatefulTable, theme: &Theme, area: Rect) {
+    // Newest first, so the latest events are visible without scrolling.
+    let items: Vec<ListItem> = table
+        .log
+        .iter()
+        .rev()
+        .map(|entry| ListItem::new(Spans::from(vec![
+            Span::styled(entry.time.format("%H:%M:%S ").to_string(), Style::default().fg(theme.muted)),
+            Span::raw(entry.message.as_str()),
+        ])))
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Activity"));
+
+    f.render_widget(list, area);
+}
+
+fn draw_rules<B: Backend>(f: &mut Frame<B>, table: &mut StatefulTable, search: Option<&Search>, theme: &Theme, area: Rect, click_areas: &mut ClickAreas) {
+    let pinned_style = Style::default()
+        .fg(theme.pinned);
+
+    let changed_style = Style::default()
+        .fg(theme.changed)
+        .add_modifier(Modifier::BOLD);
+
+    let rows = table.rule_rows();
+
+    if table.rules_state.selected().is_some_and(|selected| selected >= rows.len()) {
+        table.rules_state.select(None);
+    }
+
+    let no_rules = table.server_info.as_ref().and_then(ServerInfo::game).filter(|game| game.has(Quirk::NoRules));
+    let mut title = match (&table.rules, &table.rules_error, no_rules) {
+        (_, Some(err), _) => format!("Rules | {}", err),
+        (Some(rules), None, _) => format!("Rules | {} cvars | * changed since the last refresh", rules.rules.len()),
+        (None, None, Some(game)) => format!("Rules | {} servers do not answer rules queries", game.name),
+        (None, None, None) => "Rules | loading".to_string(),
+    };
+
+    if let Some(search) = search {
+        title = format!("{} | Search: {} ({})", title, search.text, rows.len());
+    }
+
+    if table.rules_refreshing && table.rules.is_some() {
+        title = format!("{} | refreshing…", title);
+    }
+
+    let items = rows.iter().map(|row| {
+        let style = if row.changed {
+            changed_style
+        } else if row.pinned {
+            pinned_style
+        } else {
+            Style::default()
+        };
+        let name = if row.changed { format!("* {}", row.name) } else { row.name.clone() };
+
+        Row::StyledData(vec![name, row.value.clone()].into_iter(), style)
+    });
+
+    click_areas.rows = rows_area(area);
+    click_areas.first_row = scroll(table.rules_state.selected(), table.first_rule, click_areas.rows.height as usize);
+    table.first_rule = click_areas.first_row;
+    let (items, mut state) = visible_rows(items, table.rules_state.selected(), table.first_rule, click_areas.rows);
+
+    let available = available_width(area.width, 2);
+    let name_width = (available as u32 * 2 / 5) as u16;
+    let widths = [Constraint::Length(name_width), Constraint::Length(available - name_width)];
+
+    let t = Table::new(["Name", "Value"].iter(), items.into_iter())
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .header_style(Style::default().fg(theme.header))
+        .highlight_style(Style::default().fg(theme.selected))
+        .highlight_symbol(HIGHLIGHT_SYMBOL)
+        .widths(&widths);
+
+    f.render_stateful_widget(t, area, &mut state);
+}
+
+fn draw_info<B: Backend>(f: &mut Frame<B>, table: &StatefulTable, theme: &Theme, area: Rect) {
+    let label_style = Style::default().fg(theme.muted);
+    let now = chrono::Utc::now();
+
+    let mut fields = vec![
+        ("Address".to_string(), table.address.clone()),
+        ("Last updated".to_string(), match table.updated_at {
+            Some(updated_at) => format!("{}s ago ({})", (now - updated_at).num_seconds(),
+                updated_at.with_timezone(&chrono::Local).format("%H:%M:%S")),
+            None => "never".to_string(),
+        }),
+        ("Ping".to_string(), table.ping.map(|ping| format!("{} ms", ping.num_milliseconds())).unwrap_or_default()),
+    ];
+
+    if let Some(server_info) = &table.server_info {
+        fields.extend(server_info.rows().into_iter().map(|row| (row[0].clone(), row[1].clone())));
+    }
+
+    // Fields the server did not send are left out.
+    let items: Vec<ListItem> = fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(label, value)| ListItem::new(Spans::from(vec![
+            Span::styled(format!("{:<14}", label), label_style),
+            Span::raw(value),
+        ])))
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Server info"));
+
+    f.render_widget(list, area);
+}
+
+fn draw_header<B: Backend>(f: &mut Frame<B>, table: &StatefulTable, window: TimeWindow, theme: &Theme, area: Rect) {
+    let now = chrono::Utc::now();
+    let players: Vec<Option<u64>> = table.history.window(window, now).map(|sample| sample.players.map(u64::from)).collect();
+
+    let title = match (&table.server_info, table.ping) {
+        (Some(server_info), Some(ping)) => format!("Players {}/{} | Ping {} ms | Last {}",
+            server_info.players, server_info.max_players, ping.num_milliseconds(), window.label()),
+        _ => format!("Players | Last {}", window.label()),
+    };
+
+    // Only the most recent samples fit, one per column.
+    let width = area.width.saturating_sub(2) as usize;
+    let players = &players[players.len().saturating_sub(width)..];
+    let data: Vec<u64> = players.iter().map(|players| players.unwrap_or(0)).collect();
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let gaps = Gaps { data: players, style: Style::default().fg(theme.error) };
+    let gaps_area = block.inner(area);
+
+    let sparkline = Sparkline::default()
+        .block(block)
+        .style(Style::default().fg(theme.chart))
+        .max(table.server_info.as_ref().map_or(0, |server_info| server_info.max_players as u64).max(1))
+        .data(&data);
+
+    f.render_widget(sparkline, area);
+    f.render_widget(gaps, gaps_area);
+}
+
+/// Marks the failed polls under a sparkline, which would draw them like an empty server.
+struct Gaps<'a> {
+    data: &'a [Option<u64>],
+    style: Style,
+}
+
+impl Widget for Gaps<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.height == 0 {
+            return;
+        }
+
+        for (i, value) in self.data.iter().enumerate().take(area.width as usize) {
+            if value.is_none() {
+                buf.get_mut(area.left() + i as u16, area.bottom() - 1)
+                    .set_symbol("×")
+                    .set_style(self.style);
+            }
+        }
+    }
+}
+
+fn draw_charts<B: Backend>(f: &mut Frame<B>, table: &StatefulTable, window: TimeWindow, theme: &Theme, area: Rect) {
+    let now = chrono::Utc::now();
+    let seconds = window.duration().num_seconds() as f64;
+    let x_labels = vec![
+        Span::raw(format!("-{}", window.label())),
+        Span::raw("now"),
+    ];
+
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    let players = table.history.players_points(window, now);
+    let max_players = table.server_info.as_ref().map_or(1, |server_info| server_info.max_players.max(1)) as f64;
+    let players_chart = Chart::new(lines(&players, Style::default().fg(theme.chart)))
+        .block(Block::default().borders(Borders::ALL).title("Players"))
+        .x_axis(Axis::default().bounds([-seconds, 0.0]).labels(x_labels.clone()))
+        .y_axis(Axis::default()
+            .bounds([0.0, max_players])
+            .labels(vec![Span::raw("0"), Span::raw(format!("{}", max_players))]));
+
+    let ping = table.history.ping_points(window, now);
+    let max_ping = ping.iter().flatten().map(|(_, ping)| *ping).fold(1.0, f64::max) * 1.2;
+    let ping_chart = Chart::new(lines(&ping, Style::default().fg(theme.ping)))
+        .block(Block::default().borders(Borders::ALL).title("Ping (ms)"))
+        .x_axis(Axis::default().bounds([-seconds, 0.0]).labels(x_labels))
+        .y_axis(Axis::default()
+            .bounds([0.0, max_ping])
+            .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", max_ping))]));
+
+    f.render_widget(players_chart, panes[0]);
+    f.render_widget(ping_chart, panes[1]);
+}
+
+/// A dataset per run of answered polls, so failed polls are left as a gap.
+fn lines(lines: &[Vec<(f64, f64)>], style: Style) -> Vec<Dataset<'_>> {
+    lines
+        .iter()
+        .map(|line| Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(style)
+            .data(line))
+        .collect()
+}
+
+fn draw_errors<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    // Newest first, like the activity log.
+    let items: Vec<ListItem> = app
+        .errors
+        .iter()
+        .rev()
+        .map(|entry| ListItem::new(Spans::from(vec![
+            Span::styled(entry.time.format("%H:%M:%S ").to_string(), Style::default().fg(app.theme.muted)),
+            Span::styled(entry.message.as_str(), Style::default().fg(app.theme.error)),
+        ])))
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!("Errors ({}) | {} close", app.errors.len(), app.keys.label(Action::ToggleErrors))));
+
+    f.render_widget(list, area);
+}
+
+/// The help line, with the keys as they are bound.
+fn help(app: &App) -> String {
+    let key = |action| app.keys.label(action);
+
+    [
+        format!("{} quit", key(Action::Quit)),
+        format!("{} switch server", key(Action::NextTab)),
+        format!("{} search", key(Action::Search)),
+        format!("{} add", key(Action::AddServer)),
+        format!("{} rename", key(Action::Rename)),
+        format!("{} remove", key(Action::Remove)),
+        format!("{}/{} sort", key(Action::NextSort), key(Action::ReverseSort)),
+        format!("{} rules", key(Action::ToggleRules)),
+        format!("{} info", key(Action::ToggleInfo)),
+        format!("{} graph", key(Action::ToggleChart)),
+        format!("{} time window", key(Action::NextTimeWindow)),
+        format!("{} activity", key(Action::ToggleLog)),
+        format!("{} errors", key(Action::ToggleErrors)),
+    ].join(" | ")
+}
+
+fn draw_status<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let error = app.tables[app.tabs.index].error.as_ref();
+
+    let text = match (&app.prompt, error, &app.message) {
+        (Some(prompt), _, _) => Spans::from(vec![
+            Span::styled(format!("{}: ", prompt.kind.label()), Style::default().fg(app.theme.changed)),
+            Span::raw(format!("{}_", prompt.text)),
+        ]),
+        (None, Some(error), _) => Spans::from(Span::styled(
+            format!("Error: {} | retrying | {} error log", error, app.keys.label(Action::ToggleErrors)),
+            Style::default().fg(app.theme.error),
+        )),
+        (None, None, Some(message)) => Spans::from(message.as_str()),
+        (None, None, None) => Spans::from(Span::styled(help(app), Style::default().fg(app.theme.muted))),
+    };
+
+    f.render_widget(Paragraph::new(text), area);
+}
+
+/// Draws the tabs and returns the first and last column of every title.
+fn draw_tabs<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) -> Vec<(u16, u16)> {
+    let alert_style = Style::default()
+        .fg(app.theme.changed)
+        .add_modifier(Modifier::BOLD);
+
+    let error_style = Style::default()
+        .fg(app.theme.error)
+        .add_modifier(Modifier::BOLD);
+
+    let titles: Vec<Spans> = app
+        .tabs
+        .titles
+        .iter()
+        .zip(app.tables.iter())
+        .enumerate()
+        .map(|(i, (title, table))| {
+            let title = match &app.search {
+                Some(_) => format!("{} {} ({})", i + 1, title, table.items.len()),
+                None => format!("{} {}", i + 1, title),
+            };
+            if table.error.is_some() {
+                Spans::from(Span::styled(format!("{} !", title), error_style))
+            } else if table.changed {
+                Spans::from(Span::styled(format!("{} *", title), alert_style))
+            } else {
+                Spans::from(title)
+            }
+        })
+        .collect();
+
+    // Every title is padded by a space on both sides and followed by a divider.
+    let mut x = area.x + 1;
+    let positions = titles
+        .iter()
+        .map(|title| {
+            let start = x + 1;
+            let end = start + (title.width() as u16).saturating_sub(1);
+            x = end + 3;
+            (start, end)
+        })
+        .collect();
+
+    let tabs = Tabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title("Servers"))
+        .highlight_style(Style::default().fg(app.theme.selected))
+        .select(app.tabs.index);
+
+    f.render_widget(tabs, area);
+    positions
+}
+
+fn draw_table<B: Backend>(f: &mut Frame<B>, table: &mut StatefulTable, watchlist: &Watchlist, search: Option<&Search>, theme: &Theme, area: Rect, click_areas: &mut ClickAreas) {
+    let selected_style = Style::default()
+        .fg(theme.selected);
+        
+    let normal_style = Style::default()
+        .fg(theme.text);
+
+    let watched_style = Style::default()
+        .fg(theme.watched)
+        .add_modifier(Modifier::BOLD);
+
+    let bot_style = Style::default()
+        .fg(theme.bots);
+
+    let search_style = Style::default()
+        .fg(theme.search);
+
+    let stale_style = Style::default()
+        .fg(theme.muted);
+
+    let (sort, reversed) = table.sort();
+    let columns = table.columns();
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| match column {
+            column if *column == sort && column.descending() != reversed => format!("{} ▼", column.label()),
+            column if *column == sort => format!("{} ▲", column.label()),
+            column => column.label().to_string(),
+        })
+        .collect();
+    let widths = if columns.len() > 3 {
+        column_widths(area.width, &[12, 15, 10, 10])
+    } else {
+        column_widths(area.width, &[30, 15])
+    };
+    
+    let stale = table.error.is_some();
+    let rows = table
+        .items
+        .iter()
+        .map(|i| {
+            let style = if stale {
+                stale_style
+            } else if watchlist.matches(&i[0]) {
+                watched_style
+            } else if looks_like_bot(&i[0]) {
+                bot_style
+            } else {
+                normal_style
+            };
+            Row::StyledData(i.iter(), style)
+        });
+    
+    let mut server_name = match &table.server_info {
+        Some(server_info) => format!("{} | Map: {} | Players: {}/{}", &server_info.name, &server_info.map, &server_info.players, &server_info.max_players),
+        _ if table.error.is_some() => "Error".to_string(),
+        _ if table.refreshing => "Loading…".to_string(),
+        _ => "Nothing to see here".to_string()
+    };
+
+    if let (Some(search), Some(players_info)) = (search, &table.players_info) {
+        server_name = format!("{} | Search: {} ({}/{})", server_name, search.text, table.items.len(), players_info.players.len());
+    }
+
+    if let (Some(_), Some(updated_at)) = (&table.error, table.updated_at) {
+        server_name = format!("{} | stale since {}", server_name, updated_at.with_timezone(&Local).format("%H:%M:%S"));
+    }
+
+    if table.refreshing && table.server_info.is_some() {
+        server_name = format!("{} | refreshing…", server_name);
+    }
+        
+    click_areas.header = header_positions(&columns, &widths, area);
+    click_areas.header_row = area.y + 1;
+    click_areas.rows = rows_area(area);
+    click_areas.first_row = scroll(table.state.selected(), table.first_row, click_areas.rows.height as usize);
+    table.first_row = click_areas.first_row;
+    let (rows, mut state) = visible_rows(rows, table.state.selected(), table.first_row, click_areas.rows);
+
+    let constraints: Vec<Constraint> = widths.iter().map(|width| Constraint::Length(*width)).collect();
+    let t = Table::new(header.iter(), rows.into_iter())
+        .block(Block::default().borders(Borders::ALL).title(server_name))
+        .header_style(Style::default().fg(theme.header))
+        .highlight_style(selected_style)
+        .highlight_symbol(HIGHLIGHT_SYMBOL)
+        .widths(&constraints);
+
+    f.render_stateful_widget(t, area, &mut state);
+
+    if let Some(search) = search {
+        // Every name is drawn after the highlight symbol or as many blanks.
+        let symbol_width = Span::raw(HIGHLIGHT_SYMBOL).width() as u16;
+        let names_area = Rect {
+            x: click_areas.rows.x + symbol_width,
+            width: widths[0].saturating_sub(symbol_width),
+            ..click_areas.rows
+        };
+        let matches = SearchMatches {
+            names: table.items.iter().skip(table.first_row).map(|row| row[0].as_str()).collect(),
+            search,
+            style: search_style,
+        };
+
+        f.render_widget(matches, names_area);
+    }
+}
+
+/// Styles the part of every name that matches the search, tui styles table cells only as a whole.
+struct SearchMatches<'a> {
+    names: Vec<&'a str>,
+    search: &'a Search,
+    style: Style,
+}
+
+impl Widget for SearchMatches<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for (y, name) in (area.top()..area.bottom()).zip(self.names) {
+            for range in self.search.find(name) {
+                let start = Span::raw(&name[..range.start]).width() as u16;
+                let end = (Span::raw(&name[..range.end]).width() as u16).min(area.width);
+
+                if start < end {
+                    buf.set_style(Rect { x: area.x + start, y, width: end - start, height: 1 }, self.style);
+                }
+            }
+        }
+    }
+}
+
+/// The first and last column of every header of a bordered table.
+fn header_positions(columns: &[SortColumn], widths: &[u16], area: Rect) -> Vec<(SortColumn, u16, u16)> {
+    let mut x = area.x + 1;
+
+    columns
+        .iter()
+        .zip(widths.iter().copied())
+        .map(|(column, width)| {
+            let position = (*column, x, x + width.saturating_sub(1));
+            x += width + 1;
+            position
+        })
+        .collect()
+}
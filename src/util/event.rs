@@ -1,4 +1,6 @@
+use std::cell::RefCell;
 use std::io;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -7,23 +9,112 @@ use std::sync::{
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use termion::event::{self as term_event, Key, MouseEvent};
 use termion::input::TermRead;
 
+use crate::server::{PlayersResponse, Response, RulesResponse, Server, ServerInfo};
+
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
+    /// The terminal was resized to the given width and height.
+    Resize(u16, u16),
     Tick,
+    /// The answer of the query worker with the given id.
+    Reply(usize, Reply),
+}
+
+/// What a query worker is asked to do.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Request {
+    Stats,
+    Rules,
+}
+
+pub enum Reply {
+    Stats(Box<Result<(ServerInfo, PlayersResponse, chrono::Duration), String>>),
+    Rules(Result<RulesResponse, String>),
+}
+
+/// Name of the query worker threads.
+pub const QUERY_WORKER: &str = "query-worker";
+
+/// Queries a server on its own thread, so slow servers do not block the interface.
+/// The thread stops once the worker is dropped.
+pub struct Worker {
+    pub id: usize,
+    requests: mpsc::Sender<Request>,
+}
+
+impl Worker {
+    /// Starts a worker that sends its replies to `events`, see `Events::sender`.
+    pub fn spawn(id: usize, mut server: Server, events: mpsc::Sender<Event<Key>>) -> Worker {
+        let (requests, rx) = mpsc::channel();
+
+        let spawned = thread::Builder::new().name(QUERY_WORKER.to_string()).spawn(move || {
+            for request in rx {
+                let reply = match request {
+                    Request::Stats => Reply::Stats(Box::new(guard(|| server.get_stats()))),
+                    Request::Rules => Reply::Rules(guard(|| server.get_rules())),
+                };
+
+                if events.send(Event::Reply(id, reply)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        if let Err(err) = spawned {
+            panic!("Failed to start a query worker, error: {}", err);
+        }
+
+        Worker { id, requests }
+    }
+
+    pub fn send(&self, request: Request) {
+        // The thread only stops when the worker is dropped.
+        let _ = self.requests.send(request);
+    }
+}
+
+thread_local! {
+    static PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Keeps the message of a panic on a query worker, called from the panic hook
+/// instead of printing over the interface. `guard` turns it into the error.
+pub fn record_panic(info: &PanicHookInfo) {
+    let message = info.to_string().replace('\n', " ");
+    PANIC.with(|panic| *panic.borrow_mut() = Some(message));
+}
+
+/// Turns a panic while querying into an error.
+fn guard<T, F: FnOnce() -> Response<T>>(query: F) -> Result<T, String> {
+    match panic::catch_unwind(AssertUnwindSafe(query)) {
+        Ok(Response::Ok(value)) => Ok(value),
+        Ok(Response::Error(err)) => Err(err.to_string()),
+        Err(payload) => {
+            let message = PANIC.with(|panic| panic.borrow_mut().take())
+                .or_else(|| payload.downcast_ref::<&str>().map(|message| format!("panicked: {}", message)))
+                .or_else(|| payload.downcast_ref::<String>().map(|message| format!("panicked: {}", message)))
+                .unwrap_or_else(|| "panicked".to_string());
+
+            Err(format!("Query worker {}", message))
+        }
+    }
 }
 
 /// A small event handler that wrap termion input and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
+    tx: mpsc::Sender<Event<Key>>,
     rx: mpsc::Receiver<Event<Key>>,
-    input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
-    tick_handle: thread::JoinHandle<()>,
 }
 
+/// How often the terminal size is checked, termion has no resize notification.
+const RESIZE_POLL: Duration = Duration::from_millis(200);
+
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub exit_key: Key,
@@ -40,44 +131,64 @@ impl Default for Config {
 }
 
 impl Events {
-    pub fn new() -> Events {
-        Events::with_config(Config::default())
-    }
-
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
-        let input_handle = {
+        {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if let Err(err) = tx.send(Event::Input(key)) {
-                            eprintln!("{}", err);
-                            return;
-                        }
-                        if !ignore_exit_key.load(Ordering::Relaxed) && key == config.exit_key {
-                            return;
-                        }
+                for event in stdin.events().flatten() {
+                    let event = match event {
+                        term_event::Event::Key(key) => Event::Input(key),
+                        term_event::Event::Mouse(mouse) => Event::Mouse(mouse),
+                        term_event::Event::Unsupported(_) => continue,
+                    };
+                    let exit = matches!(event, Event::Input(key) if key == config.exit_key);
+
+                    if let Err(err) = tx.send(event) {
+                        eprintln!("{}", err);
+                        return;
+                    }
+                    if !ignore_exit_key.load(Ordering::Relaxed) && exit {
+                        return;
                     }
                 }
-            })
-        };
-        let tick_handle = {
+            });
+        }
+        {
+            let tx = tx.clone();
             thread::spawn(move || loop {
                 if tx.send(Event::Tick).is_err() {
                     break;
                 }
                 thread::sleep(config.tick_rate);
-            })
-        };
+            });
+        }
+        {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut size = termion::terminal_size().ok();
+                loop {
+                    thread::sleep(RESIZE_POLL);
+
+                    let current = termion::terminal_size().ok();
+                    if current != size {
+                        size = current;
+                        if let Some((width, height)) = current {
+                            if tx.send(Event::Resize(width, height)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            });
+        }
         Events {
+            tx,
             rx,
             ignore_exit_key,
-            input_handle,
-            tick_handle,
         }
     }
 
@@ -85,11 +196,24 @@ impl Events {
         self.rx.recv()
     }
 
+    /// Sender for events from other threads, like query workers.
+    pub fn sender(&self) -> mpsc::Sender<Event<Key>> {
+        self.tx.clone()
+    }
+
     pub fn disable_exit_key(&mut self) {
         self.ignore_exit_key.store(true, Ordering::Relaxed);
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_keeps_the_panic_message() {
+        let result: Result<(), String> = guard(|| panic!("index out of bounds"));
 
-    pub fn enable_exit_key(&mut self) {
-        self.ignore_exit_key.store(false, Ordering::Relaxed);
+        assert!(result.unwrap_err().contains("index out of bounds"));
     }
 }
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use crate::server::{ServerInfo, ServerVisibility};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    MapChanged { from: String, to: String },
+    WentOffline { error: String },
+    CameOnline,
+    PlayersReached { threshold: u8, players: u8 },
+    PlayersDropped { threshold: u8, players: u8 },
+    PasswordChanged { required: bool },
+    VacChanged { secured: bool },
+    VersionChanged { from: String, to: String },
+    NameChanged { from: String, to: String },
+}
+
+impl ServerEvent {
+    /// Short machine readable name of the event, used in the history database.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerEvent::MapChanged { .. }      => "map_changed",
+            ServerEvent::WentOffline { .. }     => "offline",
+            ServerEvent::CameOnline             => "online",
+            ServerEvent::PlayersReached { .. }  => "players_reached",
+            ServerEvent::PlayersDropped { .. }  => "players_dropped",
+            ServerEvent::PasswordChanged { .. } => "password_changed",
+            ServerEvent::VacChanged { .. }      => "vac_changed",
+            ServerEvent::VersionChanged { .. }  => "version_changed",
+            ServerEvent::NameChanged { .. }     => "name_changed",
+        }
+    }
+}
+
+impl fmt::Display for ServerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServerEvent::MapChanged { from, to } => write!(f, "map changed from {} to {}", from, to),
+            ServerEvent::WentOffline { error } => write!(f, "server went offline: {}", error),
+            ServerEvent::CameOnline => write!(f, "server is back online"),
+            ServerEvent::PlayersReached { threshold, players } => write!(f, "{} players, reached {}", players, threshold),
+            ServerEvent::PlayersDropped { threshold, players } => write!(f, "{} players, dropped below {}", players, threshold),
+            ServerEvent::PasswordChanged { required: true } => write!(f, "server now requires a password"),
+            ServerEvent::PasswordChanged { required: false } => write!(f, "server no longer requires a password"),
+            ServerEvent::VacChanged { secured: true } => write!(f, "VAC enabled"),
+            ServerEvent::VacChanged { secured: false } => write!(f, "VAC disabled"),
+            ServerEvent::VersionChanged { from, to } => write!(f, "version changed from {} to {}", from, to),
+            ServerEvent::NameChanged { from, to } => write!(f, "server renamed from {} to {}", from, to),
+        }
+    }
+}
+
+/// Turns successive polls of one server into `ServerEvent`s.
+#[derive(Debug, Default)]
+pub struct ServerWatcher {
+    previous: Option<ServerInfo>,
+    /// `None` until the first successful poll, a server that never answered did not go
+    /// offline nor come back.
+    online: Option<bool>,
+}
+
+impl ServerWatcher {
+    pub fn new() -> Self {
+        ServerWatcher::default()
+    }
+
+    /// Compares a successful poll with the last one, `thresholds` are player counts that
+    /// raise an event when reached or dropped below. The first poll only sets the baseline.
+    pub fn update(&mut self, current: &ServerInfo, thresholds: &[u8]) -> Vec<ServerEvent> {
+        let mut events = vec![];
+
+        if self.online == Some(false) {
+            events.push(ServerEvent::CameOnline);
+        }
+
+        if let Some(previous) = &self.previous {
+            if previous.map != current.map {
+                events.push(ServerEvent::MapChanged { from: previous.map.clone(), to: current.map.clone() });
+            }
+
+            for &threshold in thresholds.iter() {
+                if previous.players < threshold && current.players >= threshold {
+                    events.push(ServerEvent::PlayersReached { threshold, players: current.players });
+                } else if previous.players >= threshold && current.players < threshold {
+                    events.push(ServerEvent::PlayersDropped { threshold, players: current.players });
+                }
+            }
+
+            if previous.server_visibility != current.server_visibility {
+                events.push(ServerEvent::PasswordChanged { required: current.server_visibility == ServerVisibility::Private });
+            }
+
+            if previous.vac != current.vac {
+                events.push(ServerEvent::VacChanged { secured: current.vac });
+            }
+
+            if previous.version != current.version {
+                events.push(ServerEvent::VersionChanged { from: previous.version.clone(), to: current.version.clone() });
+            }
+
+            if previous.name != current.name {
+                events.push(ServerEvent::NameChanged { from: previous.name.clone(), to: current.name.clone() });
+            }
+        }
+
+        self.previous = Some(current.clone());
+        self.online = Some(true);
+        events
+    }
+
+    /// Records a failed poll, only the first failure after being online is an event.
+    pub fn failed(&mut self, error: &str) -> Vec<ServerEvent> {
+        if self.online == Some(true) {
+            self.online = Some(false);
+            vec![ServerEvent::WentOffline { error: error.to_string() }]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fixtures::server_info;
+
+    #[test]
+    fn test_first_poll_is_the_baseline() {
+        let mut watcher = ServerWatcher::new();
+
+        assert!(watcher.update(&server_info("de_dust2", 5), &[1]).is_empty());
+        assert!(watcher.update(&server_info("de_dust2", 5), &[1]).is_empty());
+    }
+
+    #[test]
+    fn test_changes_and_thresholds() {
+        let mut watcher = ServerWatcher::new();
+        watcher.update(&server_info("de_dust2", 15), &[10, 20]);
+
+        let current = ServerInfo {
+            server_visibility: ServerVisibility::Private,
+            vac: false,
+            version: "1.0.0.1".to_string(),
+            name: "Renamed".to_string(),
+            ..server_info("de_inferno", 9)
+        };
+
+        assert_eq!(
+            vec![
+                ServerEvent::MapChanged { from: "de_dust2".to_string(), to: "de_inferno".to_string() },
+                ServerEvent::PlayersDropped { threshold: 10, players: 9 },
+                ServerEvent::PasswordChanged { required: true },
+                ServerEvent::VacChanged { secured: false },
+                ServerEvent::VersionChanged { from: "1.0.0.0".to_string(), to: "1.0.0.1".to_string() },
+                ServerEvent::NameChanged { from: "Test server".to_string(), to: "Renamed".to_string() },
+            ],
+            watcher.update(&current, &[10, 20]));
+        assert_eq!(
+            vec![ServerEvent::PlayersReached { threshold: 10, players: 20 }, ServerEvent::PlayersReached { threshold: 20, players: 20 }],
+            watcher.update(&ServerInfo { players: 20, ..current }, &[10, 20]));
+    }
+
+    #[test]
+    fn test_offline_and_back_online() {
+        let mut watcher = ServerWatcher::new();
+        watcher.update(&server_info("de_dust2", 5), &[]);
+
+        assert_eq!(vec![ServerEvent::WentOffline { error: "timed out".to_string() }], watcher.failed("timed out"));
+        assert!(watcher.failed("timed out").is_empty());
+        assert_eq!(vec![ServerEvent::CameOnline], watcher.update(&server_info("de_dust2", 5), &[]));
+    }
+
+    #[test]
+    fn test_first_poll_fails() {
+        let mut watcher = ServerWatcher::new();
+
+        assert!(watcher.failed("timed out").is_empty());
+        assert!(watcher.update(&server_info("de_dust2", 5), &[]).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::io::{self, Write};
+use std::process::Command;
+use std::thread;
+
+/// The `[watchlist]` section of the config file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchlistConfig {
+    /// Player names, `*` and `?` work as wildcards and case is ignored.
+    pub players: Vec<String>,
+    /// Ring the terminal bell when a watched player shows up.
+    pub bell: bool,
+    /// Shell command to run, it gets `WATCH_PLAYER`, `WATCH_SERVER`, `WATCH_ADDRESS` and
+    /// `WATCH_EVENT` as environment variables, e.g. `notify-send "$WATCH_PLAYER is on $WATCH_SERVER"`.
+    pub command: Option<String>,
+    /// URL that gets a JSON POST, the `content` field makes it work with Discord webhooks.
+    pub webhook: Option<String>,
+    /// Also alert on server events: map changes, going offline, password changes, ...
+    pub server_events: bool,
+    /// Player counts that raise a server event when reached or dropped below.
+    pub player_thresholds: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// The watched player, empty for server events.
+    pub player: String,
+    /// The server event, `None` for watched players.
+    pub event: Option<String>,
+    pub server: String,
+    pub address: String,
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        match &self.event {
+            Some(event) => format!("{}: {}", self.server, event),
+            None => format!("{} is on {}", self.player, self.server),
+        }
+    }
+}
+
+pub struct Watchlist {
+    patterns: Vec<String>,
+    config: WatchlistConfig,
+}
+
+impl Watchlist {
+    pub fn new(config: WatchlistConfig, extra_patterns: &[String]) -> Self {
+        let patterns = config.players
+            .iter()
+            .chain(extra_patterns.iter())
+            .map(|pattern| pattern.to_lowercase())
+            .collect();
+
+        Watchlist { patterns, config }
+    }
+
+    pub fn alerts_server_events(&self) -> bool {
+        self.config.server_events
+    }
+
+    pub fn player_thresholds(&self) -> &[u8] {
+        &self.config.player_thresholds
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(&pattern.chars().collect::<Vec<_>>(), &name))
+    }
+
+    /// Rings the bell, runs the command and calls the webhook as configured.
+    /// The command and webhook run in the background, only spawn errors are returned.
+    pub fn notify(&self, alert: &Alert) -> Result<(), String> {
+        if self.config.bell {
+            print!("\x07");
+            io::stdout().flush().map_err(|err| format!("Failed to ring the bell, error: {}", err))?;
+        }
+
+        if let Some(command) = &self.config.command {
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("WATCH_PLAYER", &alert.player)
+                .env("WATCH_SERVER", &alert.server)
+                .env("WATCH_ADDRESS", &alert.address)
+                .env("WATCH_EVENT", alert.event.as_deref().unwrap_or(""))
+                .spawn()
+                .map_err(|err| format!("Failed to run the watchlist command, error: {}", err))?;
+
+            thread::spawn(move || child.wait());
+        }
+
+        if let Some(url) = &self.config.webhook {
+            let url = url.clone();
+            let body = serde_json::json!({
+                "content": alert.message(),
+                "player": alert.player,
+                "server": alert.server,
+                "address": alert.address,
+                "event": alert.event,
+            });
+
+            // Nobody is around to see a failed webhook call, so its result is dropped.
+            thread::spawn(move || {
+                let _ = ureq::post(&url)
+                    .set("Content-Type", "application/json")
+                    .send_string(&body.to_string());
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Matches `*` (any run of characters) and `?` (any single character).
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some((c, rest)) => match name.split_first() {
+            Some((n, name_rest)) if *c == '?' || c == n => glob_match(rest, name_rest),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watchlist(patterns: &[&str]) -> Watchlist {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+
+        Watchlist::new(WatchlistConfig::default(), &patterns)
+    }
+
+    #[test]
+    fn test_exact_names_ignore_case() {
+        let watchlist = watchlist(&["Alice"]);
+
+        assert!(watchlist.matches("alice"));
+        assert!(watchlist.matches("ALICE"));
+        assert!(!watchlist.matches("alice2"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let watchlist = watchlist(&["[clan]*", "b?b"]);
+
+        assert!(watchlist.matches("[CLAN] alice"));
+        assert!(watchlist.matches("[clan]"));
+        assert!(watchlist.matches("bob"));
+        assert!(!watchlist.matches("bobby"));
+        assert!(!watchlist.matches("alice [clan]"));
+    }
+
+    #[test]
+    fn test_config_and_extra_patterns_are_combined() {
+        let config = WatchlistConfig { players: vec!["alice".to_string()], ..WatchlistConfig::default() };
+        let watchlist = Watchlist::new(config, &["bob".to_string()]);
+
+        assert!(watchlist.matches("alice"));
+        assert!(watchlist.matches("bob"));
+        assert!(!watchlist.matches("carol"));
+    }
+}